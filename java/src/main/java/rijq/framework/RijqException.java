@@ -0,0 +1,18 @@
+package rijq.framework;
+
+import rijq.framework.obj.enums.ErrorType;
+
+public class RijqException extends RuntimeException {
+
+    private final ErrorType errorType;
+
+    public RijqException(ErrorType errorType, String message) {
+        super(message);
+        this.errorType = errorType;
+    }
+
+    public ErrorType getErrorType() {
+        return errorType;
+    }
+
+}
@@ -0,0 +1,12 @@
+package rijq.framework;
+
+/**
+ * native层的panic或JNI错误, message中包含rust侧的错误信息和backtrace
+ */
+public class RijqNativeException extends RuntimeException {
+
+    public RijqNativeException(String message) {
+        super(message);
+    }
+
+}
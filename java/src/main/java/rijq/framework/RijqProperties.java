@@ -0,0 +1,245 @@
+package rijq.framework;
+
+import com.google.protobuf.ByteString;
+import lombok.Data;
+import org.springframework.boot.context.properties.ConfigurationProperties;
+import org.springframework.stereotype.Component;
+import rijq.framework.obj.*;
+import rijq.framework.obj.enums.*;
+
+import java.util.ArrayList;
+import java.util.HashMap;
+import java.util.HashSet;
+import java.util.HexFormat;
+import java.util.List;
+import java.util.Map;
+
+/**
+ * application.properties 中 rijq.* 的配置, 启动时转换为 RijqConfig 传递给native, 由native校验
+ */
+@Data
+@Component
+@ConfigurationProperties(prefix = "rijq")
+public class RijqProperties {
+
+    private LoginProperties login = new LoginProperties();
+    private SessionProperties session = new SessionProperties();
+    private StoreProperties store = new StoreProperties();
+    private DeviceProperties device = new DeviceProperties();
+    private RuntimeProperties runtime = new RuntimeProperties();
+    private ReconnectProperties reconnect = new ReconnectProperties();
+    private EventQueueProperties eventQueue = new EventQueueProperties();
+    private DispatchProperties dispatch = new DispatchProperties();
+    private LogProperties log = new LogProperties();
+    /**
+     * 多个机器人时每个机器人的登录、会话和设备配置, 为空时只使用 rijq.login / rijq.session / rijq.device 运行一个机器人
+     */
+    private List<BotProperties> bots = new ArrayList<>();
+
+    @Data
+    public static class BotProperties {
+        private LoginProperties login = new LoginProperties();
+        private SessionProperties session = new SessionProperties();
+        private DeviceProperties device = new DeviceProperties();
+    }
+
+    @Data
+    public static class LoginProperties {
+        private LoginMethod method = LoginMethod.QrCode;
+        private long uin;
+        private String password = "";
+        /**
+         * hex格式的密码MD5
+         */
+        private String passwordMd5 = "";
+        private CaptchaProperties captcha = new CaptchaProperties();
+    }
+
+    @Data
+    public static class CaptchaProperties {
+        private List<CaptchaSolverType> solvers = new ArrayList<>();
+        /**
+         * 秒, 0为默认的300秒
+         */
+        private long timeout;
+        private String callbackAddr = "";
+    }
+
+    @Data
+    public static class SessionProperties {
+        private SessionStoreType store = SessionStoreType.File;
+        private String path = "";
+    }
+
+    @Data
+    public static class StoreProperties {
+        /**
+         * hex格式的32字节AES密钥
+         */
+        private String key = "";
+    }
+
+    @Data
+    public static class DeviceProperties {
+        private Protocol protocol = Protocol.AndroidWatch;
+        private String path = "";
+        /**
+         * 覆盖设备文件中的字段, key为 device.json 中的字段名, 下划线写作中划线, 例如 finger-print
+         */
+        private Map<String, String> info = new HashMap<>();
+    }
+
+    @Data
+    public static class RuntimeProperties {
+        private int workerThreads = 10;
+        private int maxBlockingThreads = 10;
+        /**
+         * 秒
+         */
+        private long threadKeepAlive = 100;
+    }
+
+    @Data
+    public static class ReconnectProperties {
+        /**
+         * 0为不限制
+         */
+        private int maxAttempts;
+        /**
+         * 毫秒
+         */
+        private long initialDelay = 1000;
+        private long maxDelay = 60000;
+        /**
+         * 每次失败后间隔乘以该值
+         */
+        private double multiplier = 2;
+        /**
+         * 0~1, 间隔随机增减的比例
+         */
+        private double jitter = 0.2;
+        /**
+         * 毫秒, 连接失败的地址在这段时间内不再使用, 0为不拉黑
+         */
+        private long blacklistDuration = 60000;
+    }
+
+    @Data
+    public static class EventQueueProperties {
+        private int capacity = 10000;
+        private OverflowStrategy overflow = OverflowStrategy.Block;
+        /**
+         * overflow为DropByType时可以丢弃的事件类名, 例如 GroupMessageEvent
+         */
+        private List<String> droppableEvents = new ArrayList<>();
+    }
+
+    @Data
+    public static class DispatchProperties {
+        /**
+         * 大于1时不同群或好友的事件会在多个线程上同时分发, 事件处理器需要是线程安全的
+         */
+        private int threads = 1;
+        /**
+         * 通过direct ByteBuffer把事件传递给Java, 不再复制到byte[]
+         */
+        private boolean directBuffer;
+    }
+
+    @Data
+    public static class LogProperties {
+        private String level = "info";
+    }
+
+    /**
+     * 每个机器人一个配置, 多个机器人时未配置的文件路径按uin(或序号)区分
+     */
+    public List<RijqConfig> toConfigs() {
+        if (bots.isEmpty()) {
+            return List.of(toConfig(login, session, device));
+        }
+        var configs = new ArrayList<RijqConfig>();
+        var sessionPaths = new HashSet<String>();
+        var devicePaths = new HashSet<String>();
+        for (int i = 0; i < bots.size(); i++) {
+            var bot = bots.get(i);
+            var name = bot.login.uin != 0 ? String.valueOf(bot.login.uin) : String.valueOf(i);
+            if (bot.session.path.isEmpty()) {
+                bot.session.path = "rijq-" + name + ".session";
+            }
+            if (bot.device.path.isEmpty()) {
+                bot.device.path = "device-" + name + ".json";
+            }
+            if (!sessionPaths.add(bot.session.path) || !devicePaths.add(bot.device.path)) {
+                throw new IllegalStateException("机器人之间的会话或设备文件路径重复 : " + name);
+            }
+            configs.add(toConfig(bot.login, bot.session, bot.device));
+        }
+        return configs;
+    }
+
+    private RijqConfig toConfig(LoginProperties login, SessionProperties session, DeviceProperties device) {
+        return RijqConfig.newBuilder()
+                .setLogin(loginConfig(login))
+                .setDevice(deviceConfig(device))
+                .setStore(StoreConfig.newBuilder()
+                        .setSessionStore(session.store)
+                        .setSessionPath(session.path)
+                        .setStoreKey(hex(store.key)))
+                .setRuntime(RuntimeConfig.newBuilder()
+                        .setWorkerThreads(runtime.workerThreads)
+                        .setMaxBlockingThreads(runtime.maxBlockingThreads)
+                        .setThreadKeepAlive(runtime.threadKeepAlive))
+                .setReconnect(ReconnectConfig.newBuilder()
+                        .setMaxAttempts(reconnect.maxAttempts)
+                        .setInitialDelay(reconnect.initialDelay)
+                        .setMaxDelay(reconnect.maxDelay)
+                        .setMultiplier(reconnect.multiplier)
+                        .setJitter(reconnect.jitter)
+                        .setBlacklistDuration(reconnect.blacklistDuration))
+                .setEventQueue(EventQueueConfig.newBuilder()
+                        .setCapacity(eventQueue.capacity)
+                        .setOverflow(eventQueue.overflow)
+                        .addAllDroppableEvents(eventQueue.droppableEvents))
+                .setDispatch(DispatchConfig.newBuilder()
+                        .setThreads(dispatch.threads)
+                        .setDirectBuffer(dispatch.directBuffer))
+                .setLogLevel(log.level)
+                .build();
+    }
+
+    private static LoginConfig loginConfig(LoginProperties login) {
+        return LoginConfig.newBuilder()
+                .setMethod(login.method)
+                .setUin(login.uin)
+                .setPassword(login.password)
+                .setPasswordMd5(hex(login.passwordMd5))
+                .addAllCaptchaSolvers(login.captcha.solvers)
+                .setCaptchaTimeout(login.captcha.timeout)
+                .setCaptchaCallbackAddr(login.captcha.callbackAddr)
+                .build();
+    }
+
+    private static DeviceConfig deviceConfig(DeviceProperties device) {
+        var info = DeviceInfo.newBuilder();
+        for (var field : DeviceInfo.getDescriptor().getFields()) {
+            var value = device.info.get(field.getName().replace('_', '-'));
+            if (value != null && !value.isEmpty()) {
+                info.setField(field, value);
+            }
+        }
+        return DeviceConfig.newBuilder()
+                .setProtocol(device.protocol)
+                .setDevicePath(device.path)
+                .setDeviceInfo(info)
+                .build();
+    }
+
+    private static ByteString hex(String value) {
+        if (value.isEmpty()) {
+            return ByteString.EMPTY;
+        }
+        return ByteString.copyFrom(HexFormat.of().parseHex(value));
+    }
+
+}
@@ -0,0 +1,20 @@
+package rijq.framework;
+
+/**
+ * 保存登录会话(token), 配置 rijq.session.store=Bean 时使用容器中的该Bean
+ * <p>
+ * 方法在native的blocking线程上调用, 抛出的异常会使本次读写失败.
+ * key为机器人的会话路径(rijq.session.path), 多个机器人共用同一个Bean时以此区分
+ */
+public interface SessionStore {
+
+    void save(String key, byte[] data);
+
+    /**
+     * @return 没有保存的会话时返回null
+     */
+    byte[] load(String key);
+
+    void remove(String key);
+
+}
@@ -3,25 +3,31 @@ package rijq.framework.handlers;
 import com.google.protobuf.ByteString;
 import org.slf4j.Logger;
 import org.slf4j.LoggerFactory;
+import org.springframework.beans.factory.DisposableBean;
 import org.springframework.boot.ApplicationArguments;
 import org.springframework.boot.ApplicationRunner;
 import org.springframework.context.ApplicationContext;
 import org.springframework.core.annotation.Order;
 import org.springframework.stereotype.Component;
+import rijq.framework.RijqException;
+import rijq.framework.RijqProperties;
+import rijq.framework.SessionStore;
 import rijq.framework.annotaions.Handler;
 import rijq.framework.annotaions.Module;
-import rijq.framework.obj.CallNativeResult;
-import rijq.framework.obj.FriendMessageEvent;
-import rijq.framework.obj.GroupMessageEvent;
-import rijq.framework.obj.LoginEvent;
+import rijq.framework.obj.*;
+import rijq.framework.obj.enums.ErrorType;
 import rijq.framework.obj.enums.ResultType;
+import rijq.framework.obj.enums.SessionStoreType;
 
 import java.lang.reflect.InvocationTargetException;
 import java.lang.reflect.Method;
 import java.util.*;
+import java.util.concurrent.CompletableFuture;
+import java.util.concurrent.ConcurrentHashMap;
+import java.util.concurrent.CopyOnWriteArrayList;
 
 @Component
-public class InitRunner implements ApplicationRunner {
+public class InitRunner implements ApplicationRunner, DisposableBean {
 
     static {
         System.loadLibrary("rijq");
@@ -29,6 +35,38 @@ public class InitRunner implements ApplicationRunner {
 
     private final Logger logger = LoggerFactory.getLogger(getClass());
 
+    private static final List<Class<?>> EVENT_CLASSES = List.of(
+            LoginEvent.class,
+            LoginQrCodeEvent.class,
+            LoginCaptchaEvent.class,
+            LoginDeviceLockEvent.class,
+            DisconnectedEvent.class,
+            ReconnectingEvent.class,
+            ReconnectedEvent.class,
+            SessionLostEvent.class,
+            GroupMessageEvent.class,
+            FriendMessageEvent.class,
+            GroupTempMessageEvent.class,
+            GroupRequestEvent.class,
+            SelfInvitedEvent.class,
+            NewFriendRequestEvent.class,
+            NewMemberEvent.class,
+            GroupMuteEvent.class,
+            FriendMessageRecallEvent.class,
+            GroupMessageRecallEvent.class,
+            NewFriendEvent.class,
+            GroupLeaveEvent.class,
+            GroupDisbandEvent.class,
+            FriendPokeEvent.class,
+            GroupPokeEvent.class,
+            GroupNameUpdateEvent.class,
+            DeleteFriendEvent.class,
+            MemberPermissionChangeEvent.class,
+            KickedOfflineEvent.class,
+            MSFOfflineEvent.class,
+            ClientDisconnectEvent.class
+    );
+
 
     private final ApplicationContext applicationContext;
     private final Map<Class, List<EventMethodPoint>> points;
@@ -40,6 +78,7 @@ public class InitRunner implements ApplicationRunner {
         public Object moduleInstance;
         public String name;
         public Method method;
+        public boolean withBotUin;
     }
 
     public InitRunner(ApplicationContext applicationContext) {
@@ -50,11 +89,64 @@ public class InitRunner implements ApplicationRunner {
     @Override
     public void run(ApplicationArguments args) throws Exception {
         var moduleBeans = getModuleBeans();
-        putPoints(LoginEvent.class, moduleBeans);
-        putPoints(GroupMessageEvent.class, moduleBeans);
-        putPoints(FriendMessageEvent.class, moduleBeans);
-        this.daemon();
-        // sendMessage(env_point, runtime_point, "1",  LoginEvent.getDefaultInstance().toByteArray());
+        for (Class<?> eventClass : EVENT_CLASSES) {
+            putPoints(eventClass, moduleBeans);
+        }
+        var configs = applicationContext.getBean(RijqProperties.class).toConfigs();
+        // 每个机器人一个daemon线程, daemon在机器人停止前不会返回
+        for (int i = 0; i < configs.size(); i++) {
+            var config = configs.get(i);
+            SessionStore sessionStore = null;
+            if (config.getStore().getSessionStore() == SessionStoreType.Bean) {
+                sessionStore = applicationContext.getBean(SessionStore.class);
+            }
+            var store = sessionStore;
+            var thread = new Thread(() -> runDaemon(config, store), "rijq-daemon-" + i);
+            daemonThreads.add(thread);
+            thread.start();
+        }
+    }
+
+    /**
+     * Spring上下文关闭时停止所有机器人, 保存会话并等待daemon返回
+     */
+    @Override
+    public void destroy() throws Exception {
+        for (Long handle : handles) {
+            // 单个机器人停止失败不影响其余机器人
+            try {
+                var result = nativeCall(handle, "Shutdown", new byte[0]);
+                if (result.getCode() != ResultType.Success) {
+                    logger.warn("停止机器人失败 : {}", result.getMessage());
+                }
+            } catch (RuntimeException e) {
+                logger.warn("停止机器人失败 : {}", handle, e);
+            }
+        }
+        for (Thread thread : daemonThreads) {
+            thread.join(DAEMON_STOP_TIMEOUT_MILLIS);
+            if (thread.isAlive()) {
+                logger.warn("daemon 线程未能在 {}ms 内结束 : {}", DAEMON_STOP_TIMEOUT_MILLIS, thread.getName());
+            }
+        }
+        daemonThreads.clear();
+    }
+
+    private void runDaemon(RijqConfig config, SessionStore sessionStore) {
+        DAEMON_UIN.set(config.getLogin().getUin());
+        try {
+            this.daemon(config.toByteArray(), sessionStore);
+        } catch (RuntimeException e) {
+            logger.error("daemon 异常结束", e);
+        } finally {
+            var handle = DAEMON_HANDLE.get();
+            DAEMON_HANDLE.remove();
+            DAEMON_UIN.remove();
+            if (handle != null) {
+                handles.remove(handle);
+                bots.values().removeIf(h -> h.equals(handle));
+            }
+        }
     }
 
     private void putPoints(Class<?> clazz, List<Object> moduleBeans) throws Exception {
@@ -71,8 +163,10 @@ public class InitRunner implements ApplicationRunner {
                 return Integer.compare(order1, order2);
             }).toList();
             for (Method method : handlerMethods) {
-                if (method.getParameterCount() != 1) {
-                    throw new RuntimeException("参数最多为1个");
+                // 第二个参数为可选的 long botUin, 即接收事件的机器人
+                var withBotUin = method.getParameterCount() == 2 && method.getParameterTypes()[1] == long.class;
+                if (method.getParameterCount() != 1 && !withBotUin) {
+                    throw new RuntimeException("参数必须为事件, 或者事件和 long botUin");
                 }
                 if (method.getReturnType() != boolean.class) {
                     throw new RuntimeException("返回值必须为 boolean 类型");
@@ -84,6 +178,7 @@ public class InitRunner implements ApplicationRunner {
                     point.moduleClass = moduleBean.getClass();
                     point.moduleInstance = moduleBean;
                     point.method = method;
+                    point.withBotUin = withBotUin;
                     points.get(clazz).add(point);
                     logger.info("注册事件处理器: {} -> {} ({})", point.moduleClass, method.getName(), clazz.getName());
                 }
@@ -106,44 +201,141 @@ public class InitRunner implements ApplicationRunner {
         return moduleBeans;
     }
 
-    public void dispatchEventMethodPoint(Object object) throws InvocationTargetException, IllegalAccessException {
+    private static final ThreadLocal<Long> CURRENT_BOT = new ThreadLocal<>();
+
+    /**
+     * 当前线程正在处理的事件所属的daemon句柄, 登录事件的botUin为0, 需要按句柄调用
+     */
+    private static final ThreadLocal<Long> CURRENT_HANDLE = new ThreadLocal<>();
+
+    static long currentBotUin() {
+        var botUin = CURRENT_BOT.get();
+        return botUin == null ? 0 : botUin;
+    }
+
+    /**
+     * rijq.dispatch.threads大于1时会在多个线程上同时调用, 同一个群或好友的事件不会同时分发
+     */
+    public void dispatchEventMethodPoint(long handle, long botUin, Object object) throws InvocationTargetException, IllegalAccessException {
+        if (botUin != 0) {
+            bots.put(botUin, handle);
+        }
         var list = points.get(object.getClass());
         if (list != null) {
-            for (EventMethodPoint eventMethodPoint : list) {
-                if ((Boolean) (eventMethodPoint.method.invoke(eventMethodPoint.moduleInstance, object))) {
-                    // todo log
-                    return;
+            CURRENT_BOT.set(botUin);
+            CURRENT_HANDLE.set(handle);
+            try {
+                for (EventMethodPoint eventMethodPoint : list) {
+                    var handled = eventMethodPoint.withBotUin
+                            ? eventMethodPoint.method.invoke(eventMethodPoint.moduleInstance, object, botUin)
+                            : eventMethodPoint.method.invoke(eventMethodPoint.moduleInstance, object);
+                    if ((Boolean) handled) {
+                        // todo log
+                        return;
+                    }
                 }
+            } finally {
+                CURRENT_BOT.remove();
+                CURRENT_HANDLE.remove();
             }
         }
     }
 
-    private long env_point;
-    private long runtime_point;
-    private long client_point;
+    /**
+     * daemon线程对应的句柄
+     */
+    private static final ThreadLocal<Long> DAEMON_HANDLE = new ThreadLocal<>();
 
-    private void setEnvPoints(long env_point, long runtime_point, long client_point) {
-        this.env_point = env_point;
-        this.runtime_point = runtime_point;
-        this.client_point = client_point;
+    /**
+     * daemon线程配置的登录uin, 扫码登录时为0
+     */
+    private static final ThreadLocal<Long> DAEMON_UIN = new ThreadLocal<>();
+
+    /**
+     * 正在运行的daemon句柄
+     */
+    private final Set<Long> handles = ConcurrentHashMap.newKeySet();
+
+    /**
+     * 机器人uin到daemon句柄, daemon启动时记录配置的uin, 收到带有uin的事件后记录
+     */
+    private final Map<Long, Long> bots = new ConcurrentHashMap<>();
+
+    private static final long DAEMON_STOP_TIMEOUT_MILLIS = 15000;
+
+    private final List<Thread> daemonThreads = new CopyOnWriteArrayList<>();
+
+    private void setHandle(long handle) {
+        DAEMON_HANDLE.set(handle);
+        handles.add(handle);
+        // 配置了uin时不需要等到登录完成就可以按uin调用
+        var uin = DAEMON_UIN.get();
+        if (uin != null && uin != 0) {
+            bots.put(uin, handle);
+        }
+    }
+
+    /**
+     * botUin为0时使用当前事件所属的机器人, 只有一个机器人时使用该机器人
+     */
+    private long resolveHandle(long botUin) {
+        if (botUin == 0) {
+            var handle = CURRENT_HANDLE.get();
+            if (handle != null) {
+                return handle;
+            }
+        }
+        if (botUin != 0) {
+            var handle = bots.get(botUin);
+            if (handle == null) {
+                throw new RijqException(ErrorType.NotFound, "bot " + botUin + " is not running");
+            }
+            return handle;
+        }
+        if (handles.size() != 1) {
+            throw new RijqException(ErrorType.InvalidArgument, handles.size() + " bots running, bot uin required");
+        }
+        return handles.iterator().next();
+    }
+
+    /**
+     * 正在运行并且配置了uin或已经收到过事件的机器人uin
+     */
+    public Set<Long> getBotUins() {
+        return Collections.unmodifiableSet(bots.keySet());
     }
 
-    private native CallNativeResult callNative(long env_point, long runtime_point, long client_point, String messageType, byte[] message);
+    private native CallNativeResult nativeCall(long handle, String messageType, byte[] message);
 
-    protected ByteString callNative(String messageType, byte[] message) {
-        var result = callNative(
-                this.env_point,
-                this.runtime_point,
-                this.client_point,
+    protected ByteString callNative(long botUin, String messageType, byte[] message) {
+        var result = nativeCall(
+                resolveHandle(botUin),
                 messageType,
                 message
         );
         if (result.getCode() != ResultType.Success) {
-            throw new RuntimeException(result.getMessage());
+            throw new RijqException(result.getErrorType(), result.getMessage());
         }
         return result.getData();
     }
 
-    private native void daemon();
+    private native void nativeCallAsync(long handle, String messageType, byte[] message, CompletableFuture<CallNativeResult> future);
+
+    /**
+     * 不阻塞调用线程, native的worker线程只负责complete, 返回的future和它的后续处理在 ForkJoinPool.commonPool 上执行,
+     * 所以后续处理中可以调用同步方法 (worker线程上调用同步方法会因为在运行时中再次block_on而失败)
+     */
+    protected CompletableFuture<ByteString> callNativeAsync(long botUin, String messageType, byte[] message) {
+        var future = new CompletableFuture<CallNativeResult>();
+        nativeCallAsync(resolveHandle(botUin), messageType, message, future);
+        return future.thenApplyAsync(result -> {
+            if (result.getCode() != ResultType.Success) {
+                throw new RijqException(result.getErrorType(), result.getMessage());
+            }
+            return result.getData();
+        });
+    }
+
+    private native void daemon(byte[] config, SessionStore sessionStore);
 
 }
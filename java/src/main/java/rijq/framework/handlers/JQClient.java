@@ -1,26 +1,69 @@
 package rijq.framework.handlers;
 
 import com.google.protobuf.ByteString;
+import com.google.protobuf.InvalidProtocolBufferException;
+import com.google.protobuf.Parser;
 import lombok.SneakyThrows;
+import org.springframework.beans.factory.annotation.Autowired;
 import org.springframework.stereotype.Component;
 import rijq.framework.obj.*;
 import rijq.framework.obj.enums.ElementType;
 import rijq.framework.obj.enums.SendTargetType;
 
+import java.util.List;
+import java.util.Map;
+import java.util.concurrent.CompletableFuture;
+import java.util.concurrent.CompletionException;
+
 @Component
 public class JQClient {
 
     private final InitRunner initRunner;
 
+    /**
+     * 0表示当前事件所属的机器人, 只有一个机器人时为该机器人
+     */
+    private final long botUin;
+
+    @Autowired
     public JQClient(InitRunner initRunner) {
+        this(initRunner, 0);
+    }
+
+    private JQClient(InitRunner initRunner, long botUin) {
         this.initRunner = initRunner;
+        this.botUin = botUin;
+    }
+
+    /**
+     * 返回指定机器人的客户端, 多个机器人时在事件处理器之外调用需要指定
+     */
+    public JQClient bot(long botUin) {
+        return new JQClient(initRunner, botUin);
     }
 
-    public void sendFriendMessage(
+    /**
+     * 当前线程正在处理的事件所属的机器人uin, 不在事件处理器中时为0
+     */
+    public static long currentBotUin() {
+        return InitRunner.currentBotUin();
+    }
+
+    /**
+     * 这个客户端对应的机器人uin
+     */
+    private long selfUin() {
+        var uin = botUin != 0 ? botUin : currentBotUin();
+        return uin != 0 ? uin : getStatus().getUin();
+    }
+
+    @SneakyThrows
+    public MessageReceipt sendFriendMessage(
             long uin,
             String text
     ) {
-        initRunner.callNative(
+        var result = initRunner.callNative(
+                botUin,
                 "SendFriendMessage",
                 SendFriendMessage.newBuilder()
                         .setTarget(uin)
@@ -29,6 +72,53 @@ public class JQClient {
                                 .setElementData(Text.newBuilder().setContent(text).build().toByteString()))
                         .build().toByteArray()
         );
+        return MessageReceipt.parseFrom(result);
+    }
+
+    @SneakyThrows
+    public MessageReceipt sendFriendMessage(
+            long uin,
+            List<MessageElement> elements
+    ) {
+        var result = initRunner.callNative(
+                botUin,
+                "SendFriendMessage",
+                SendFriendMessage.newBuilder()
+                        .setTarget(uin)
+                        .addAllElements(elements)
+                        .build().toByteArray()
+        );
+        return MessageReceipt.parseFrom(result);
+    }
+
+    @SneakyThrows
+    public MessageReceipt sendGroupMessage(
+            long groupCode,
+            String text
+    ) {
+        return sendGroupMessage(
+                groupCode,
+                List.of(MessageElement.newBuilder()
+                        .setElementType(ElementType.Text)
+                        .setElementData(Text.newBuilder().setContent(text).build().toByteString())
+                        .build())
+        );
+    }
+
+    @SneakyThrows
+    public MessageReceipt sendGroupMessage(
+            long groupCode,
+            List<MessageElement> elements
+    ) {
+        var result = initRunner.callNative(
+                botUin,
+                "SendGroupMessage",
+                SendGroupMessage.newBuilder()
+                        .setGroupCode(groupCode)
+                        .addAllElements(elements)
+                        .build().toByteArray()
+        );
+        return MessageReceipt.parseFrom(result);
     }
 
 
@@ -38,6 +128,7 @@ public class JQClient {
             byte[] buff
     ) {
         var result = initRunner.callNative(
+                botUin,
                 "UploadImage",
                 UploadImageDto.newBuilder()
                         .setTargetType(SendTargetType.Friend)
@@ -55,6 +146,7 @@ public class JQClient {
             byte[] buff
     ) {
         var result = initRunner.callNative(
+                botUin,
                 "UploadImage",
                 UploadImageDto.newBuilder()
                         .setTargetType(SendTargetType.Group)
@@ -65,4 +157,429 @@ public class JQClient {
         return FriendImage.parseFrom(result);
     }
 
+    public void acceptFriendRequest(NewFriendRequestEvent event) {
+        solveFriendRequest(event, true);
+    }
+
+    public void rejectFriendRequest(NewFriendRequestEvent event) {
+        solveFriendRequest(event, false);
+    }
+
+    public void solveFriendRequest(
+            NewFriendRequestEvent event,
+            boolean accept
+    ) {
+        initRunner.callNative(
+                botUin,
+                "SolveFriendRequest",
+                SolveFriendRequest.newBuilder()
+                        .setMsgSeq(event.getMsgSeq())
+                        .setReqUin(event.getReqUin())
+                        .setAccept(accept)
+                        .build().toByteArray()
+        );
+    }
+
+    public void acceptGroupRequest(GroupRequestEvent event) {
+        solveGroupRequest(event, true, "", false);
+    }
+
+    public void rejectGroupRequest(
+            GroupRequestEvent event,
+            String reason,
+            boolean block
+    ) {
+        solveGroupRequest(event, false, reason, block);
+    }
+
+    public void solveGroupRequest(
+            GroupRequestEvent event,
+            boolean accept,
+            String reason,
+            boolean block
+    ) {
+        initRunner.callNative(
+                botUin,
+                "SolveGroupRequest",
+                SolveGroupRequest.newBuilder()
+                        .setMsgSeq(event.getMsgSeq())
+                        .setReqUin(event.getReqUin())
+                        .setGroupCode(event.getGroupCode())
+                        .setSuspicious(event.getSuspicious())
+                        .setAccept(accept)
+                        .setBlock(block)
+                        .setReason(reason)
+                        .build().toByteArray()
+        );
+    }
+
+    public void acceptSelfInvited(SelfInvitedEvent event) {
+        solveSelfInvited(event, true);
+    }
+
+    public void rejectSelfInvited(SelfInvitedEvent event) {
+        solveSelfInvited(event, false);
+    }
+
+    public void solveSelfInvited(
+            SelfInvitedEvent event,
+            boolean accept
+    ) {
+        initRunner.callNative(
+                botUin,
+                "SolveSelfInvited",
+                SolveSelfInvited.newBuilder()
+                        .setMsgSeq(event.getMsgSeq())
+                        .setInvitorUin(event.getInvitorUin())
+                        .setGroupCode(event.getGroupCode())
+                        .setAccept(accept)
+                        .build().toByteArray()
+        );
+    }
+
+    public void muteMember(
+            long groupCode,
+            long memberUin,
+            long seconds
+    ) {
+        initRunner.callNative(
+                botUin,
+                "GroupMuteMember",
+                GroupMuteMember.newBuilder()
+                        .setGroupCode(groupCode)
+                        .setMemberUin(memberUin)
+                        .setDuration(seconds)
+                        .build().toByteArray()
+        );
+    }
+
+    public void unmuteMember(
+            long groupCode,
+            long memberUin
+    ) {
+        muteMember(groupCode, memberUin, 0);
+    }
+
+    public void muteAll(
+            long groupCode,
+            boolean mute
+    ) {
+        initRunner.callNative(
+                botUin,
+                "GroupMuteAll",
+                GroupMuteAll.newBuilder()
+                        .setGroupCode(groupCode)
+                        .setMute(mute)
+                        .build().toByteArray()
+        );
+    }
+
+    public void kickMembers(
+            long groupCode,
+            List<Long> memberUins,
+            String kickMsg,
+            boolean block
+    ) {
+        initRunner.callNative(
+                botUin,
+                "GroupKick",
+                GroupKick.newBuilder()
+                        .setGroupCode(groupCode)
+                        .addAllMemberUins(memberUins)
+                        .setKickMsg(kickMsg)
+                        .setBlock(block)
+                        .build().toByteArray()
+        );
+    }
+
+    public void setAdmin(
+            long groupCode,
+            long memberUin,
+            boolean admin
+    ) {
+        initRunner.callNative(
+                botUin,
+                "GroupSetAdmin",
+                GroupSetAdmin.newBuilder()
+                        .setGroupCode(groupCode)
+                        .setMemberUin(memberUin)
+                        .setAdmin(admin)
+                        .build().toByteArray()
+        );
+    }
+
+    public void editCard(
+            long groupCode,
+            long memberUin,
+            String card
+    ) {
+        initRunner.callNative(
+                botUin,
+                "GroupEditCard",
+                GroupEditCard.newBuilder()
+                        .setGroupCode(groupCode)
+                        .setMemberUin(memberUin)
+                        .setCard(card)
+                        .build().toByteArray()
+        );
+    }
+
+    public void editSpecialTitle(
+            long groupCode,
+            long memberUin,
+            String title
+    ) {
+        initRunner.callNative(
+                botUin,
+                "GroupEditSpecialTitle",
+                GroupEditSpecialTitle.newBuilder()
+                        .setGroupCode(groupCode)
+                        .setMemberUin(memberUin)
+                        .setTitle(title)
+                        .build().toByteArray()
+        );
+    }
+
+    /**
+     * 连接状态、在线时间和事件队列长度, 可用于健康检查
+     */
+    @SneakyThrows
+    public BotStatus getStatus() {
+        var result = initRunner.callNative(botUin, "GetStatus", new byte[0]);
+        return BotStatus.parseFrom(result);
+    }
+
+    /**
+     * 事件队列满时被丢弃的事件数, key为事件类名
+     */
+    public Map<String, Long> getDroppedEvents() {
+        return getStatus().getDroppedEventsMap();
+    }
+
+    @SneakyThrows
+    public FriendList getFriendList() {
+        var result = initRunner.callNative(botUin, "GetFriendList", new byte[0]);
+        return FriendList.parseFrom(result);
+    }
+
+    @SneakyThrows
+    public GroupList getGroupList() {
+        var result = initRunner.callNative(botUin, "GetGroupList", new byte[0]);
+        return GroupList.parseFrom(result);
+    }
+
+    @SneakyThrows
+    public GroupMemberList getGroupMemberList(long groupCode) {
+        var result = initRunner.callNative(
+                botUin,
+                "GetGroupMemberList",
+                GetGroupMemberList.newBuilder()
+                        .setGroupCode(groupCode)
+                        .build().toByteArray()
+        );
+        return GroupMemberList.parseFrom(result);
+    }
+
+    public void recallFriendMessage(FriendMessageEvent event) {
+        // 其他设备上发送的消息同步过来时, fromUin是机器人自己
+        var target = event.getFromUin() == selfUin() ? event.getTarget() : event.getFromUin();
+        initRunner.callNative(
+                botUin,
+                "RecallFriendMessage",
+                RecallFriendMessage.newBuilder()
+                        .setTarget(target)
+                        .addAllSeqs(event.getSeqsList())
+                        .addAllRands(event.getRandsList())
+                        .setTime(event.getTime())
+                        .build().toByteArray()
+        );
+    }
+
+    public void recallFriendMessage(
+            long uin,
+            MessageReceipt receipt
+    ) {
+        initRunner.callNative(
+                botUin,
+                "RecallFriendMessage",
+                RecallFriendMessage.newBuilder()
+                        .setTarget(uin)
+                        .addAllSeqs(receipt.getSeqsList())
+                        .addAllRands(receipt.getRandsList())
+                        .setTime(receipt.getTime())
+                        .build().toByteArray()
+        );
+    }
+
+    public void recallGroupMessage(GroupMessageEvent event) {
+        initRunner.callNative(
+                botUin,
+                "RecallGroupMessage",
+                RecallGroupMessage.newBuilder()
+                        .setGroupCode(event.getGroupCode())
+                        .addAllSeqs(event.getSeqsList())
+                        .addAllRands(event.getRandsList())
+                        .build().toByteArray()
+        );
+    }
+
+    public void recallGroupMessage(
+            long groupCode,
+            MessageReceipt receipt
+    ) {
+        initRunner.callNative(
+                botUin,
+                "RecallGroupMessage",
+                RecallGroupMessage.newBuilder()
+                        .setGroupCode(groupCode)
+                        .addAllSeqs(receipt.getSeqsList())
+                        .addAllRands(receipt.getRandsList())
+                        .build().toByteArray()
+        );
+    }
+
+    /**
+     * 设备锁时请求发送短信验证码
+     */
+    public void requestSms() {
+        initRunner.callNative(botUin, "RequestSms", new byte[0]);
+    }
+
+    /**
+     * 设备锁时提交短信验证码
+     */
+    public void submitSmsCode(String code) {
+        initRunner.callNative(
+                botUin,
+                "SubmitSmsCode",
+                SubmitSmsCode.newBuilder()
+                        .setCode(code)
+                        .build().toByteArray()
+        );
+    }
+
+    /**
+     * 设备锁时已经打开验证地址完成验证, 重新登录
+     */
+    public void confirmDeviceLock() {
+        initRunner.callNative(botUin, "ConfirmDeviceLock", new byte[0]);
+    }
+
+    /**
+     * 提交滑动条验证得到的ticket
+     */
+    public void submitTicket(String ticket) {
+        initRunner.callNative(
+                botUin,
+                "SubmitTicket",
+                SubmitTicket.newBuilder()
+                        .setTicket(ticket)
+                        .build().toByteArray()
+        );
+    }
+
+    /**
+     * 取消正在等待的设备锁或滑动条验证, 登录将失败
+     */
+    public void cancelLogin() {
+        initRunner.callNative(botUin, "CancelLogin", new byte[0]);
+    }
+
+    /**
+     * 断开连接并保存会话, 之后daemon返回, 机器人不能再被调用
+     */
+    public void shutdown() {
+        initRunner.callNative(botUin, "Shutdown", new byte[0]);
+    }
+
+    /**
+     * 断开连接并保存会话, 然后重新连接和登录, 优先使用保存的会话
+     */
+    public void restart() {
+        initRunner.callNative(botUin, "Restart", new byte[0]);
+    }
+
+    public CompletableFuture<MessageReceipt> sendFriendMessageAsync(
+            long uin,
+            List<MessageElement> elements
+    ) {
+        return parseAsync(
+                initRunner.callNativeAsync(
+                        botUin,
+                        "SendFriendMessage",
+                        SendFriendMessage.newBuilder()
+                                .setTarget(uin)
+                                .addAllElements(elements)
+                                .build().toByteArray()
+                ),
+                MessageReceipt.parser()
+        );
+    }
+
+    public CompletableFuture<MessageReceipt> sendGroupMessageAsync(
+            long groupCode,
+            List<MessageElement> elements
+    ) {
+        return parseAsync(
+                initRunner.callNativeAsync(
+                        botUin,
+                        "SendGroupMessage",
+                        SendGroupMessage.newBuilder()
+                                .setGroupCode(groupCode)
+                                .addAllElements(elements)
+                                .build().toByteArray()
+                ),
+                MessageReceipt.parser()
+        );
+    }
+
+    public CompletableFuture<FriendImage> uploadFriendImageAsync(
+            long uin,
+            byte[] buff
+    ) {
+        return parseAsync(
+                initRunner.callNativeAsync(
+                        botUin,
+                        "UploadImage",
+                        UploadImageDto.newBuilder()
+                                .setTargetType(SendTargetType.Friend)
+                                .setTarget(uin)
+                                .setData(ByteString.copyFrom(buff))
+                                .build().toByteArray()
+                ),
+                FriendImage.parser()
+        );
+    }
+
+    public CompletableFuture<GroupImage> uploadGroupImageAsync(
+            long groupNumber,
+            byte[] buff
+    ) {
+        return parseAsync(
+                initRunner.callNativeAsync(
+                        botUin,
+                        "UploadImage",
+                        UploadImageDto.newBuilder()
+                                .setTargetType(SendTargetType.Group)
+                                .setTarget(groupNumber)
+                                .setData(ByteString.copyFrom(buff))
+                                .build().toByteArray()
+                ),
+                GroupImage.parser()
+        );
+    }
+
+    private static <T> CompletableFuture<T> parseAsync(
+            CompletableFuture<ByteString> future,
+            Parser<T> parser
+    ) {
+        return future.thenApply(data -> {
+            try {
+                return parser.parseFrom(data);
+            } catch (InvalidProtocolBufferException e) {
+                throw new CompletionException(e);
+            }
+        });
+    }
+
 }
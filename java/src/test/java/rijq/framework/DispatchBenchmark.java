@@ -0,0 +1,49 @@
+package rijq.framework;
+
+import rijq.framework.obj.GroupMessageEvent;
+
+import java.util.concurrent.atomic.AtomicLong;
+
+/**
+ * 测量ricq群消息事件经过native的事件转换、事件队列和分发线程交给Java的吞吐量, 使用 make bench 运行
+ */
+public class DispatchBenchmark {
+
+    static {
+        System.loadLibrary("rijq");
+    }
+
+    private static final int WARMUP_EVENTS = 100_000;
+    private static final int EVENTS = 1_000_000;
+
+    private final AtomicLong received = new AtomicLong();
+
+    /**
+     * 返回分发完所有事件的纳秒数
+     */
+    private native long run(int events, int threads, boolean directBuffer);
+
+    public void dispatchEventMethodPoint(long handle, long botUin, Object event) {
+        if (((GroupMessageEvent) event).getElementsCount() != 1) {
+            throw new IllegalStateException("事件解析错误");
+        }
+        received.incrementAndGet();
+    }
+
+    public static void main(String[] args) {
+        var benchmark = new DispatchBenchmark();
+        for (boolean directBuffer : new boolean[]{false, true}) {
+            for (int threads : new int[]{1, 2, 4, 8}) {
+                benchmark.run(WARMUP_EVENTS, threads, directBuffer);
+                benchmark.received.set(0);
+                long nanos = benchmark.run(EVENTS, threads, directBuffer);
+                if (benchmark.received.get() != EVENTS) {
+                    throw new IllegalStateException("收到 " + benchmark.received.get() + " 个事件, 应为 " + EVENTS);
+                }
+                System.out.printf("threads=%d directBuffer=%b : %.0f events/s%n",
+                        threads, directBuffer, EVENTS * 1e9 / nanos);
+            }
+        }
+    }
+
+}
@@ -0,0 +1,102 @@
+use jni::objects::JObject;
+use jni::sys::{jboolean, jint, jlong, JNI_TRUE};
+use jni::JNIEnv;
+use ricq::client::event::GroupMessageEvent;
+use ricq::handler::QEvent;
+use ricq_core::msg::elem;
+use ricq_core::msg::MessageChain;
+use ricq_core::protocol::device::Device;
+use ricq_core::structs::GroupMessage;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::dispatch::{run_shards, Dispatcher};
+use crate::event::BotEvent;
+use crate::obj::enums::OverflowStrategy;
+use crate::queue::EventQueue;
+use crate::status::Status;
+use crate::{device, error, obj, JHandler};
+
+/// 事件平均分布在这些群中
+const GROUPS: i64 = 100;
+
+/// 只在bench feature下编译, 由 rijq.framework.DispatchBenchmark 调用, 返回分发完所有事件的纳秒数
+#[no_mangle]
+pub extern "system" fn Java_rijq_framework_DispatchBenchmark_run(
+    mut env: JNIEnv,
+    runner: JObject,
+    events: jint,
+    threads: jint,
+    direct_buffer: jboolean,
+) -> jlong {
+    error::catch_jni(&mut env, 0, |env| {
+        run(env, &runner, events, threads, direct_buffer == JNI_TRUE)
+    })
+}
+
+/// 另一个线程不断放入ricq的群消息事件, 经过和daemon相同的转换、队列和分发线程交给Java
+///
+/// 不包括ricq接收和解析数据包, 构造事件的耗时计入结果
+fn run(
+    env: &mut JNIEnv,
+    runner: &JObject,
+    events: jint,
+    threads: jint,
+    direct_buffer: bool,
+) -> anyhow::Result<jlong> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()?;
+    let queue = Arc::new(EventQueue::new(
+        &obj::EventQueueConfig {
+            capacity: 10000,
+            overflow: OverflowStrategy::Block as i32,
+            droppable_events: vec![],
+        },
+        threads.max(1) as usize,
+    ));
+    let status = Arc::new(Status::default());
+    // 只用于构造事件, 不会连接服务器
+    let client = Arc::new(ricq::Client::new(
+        Device::random(),
+        device::protocol(&Default::default())?,
+        JHandler {
+            events: queue.clone(),
+            status: status.clone(),
+        },
+    ));
+    let dispatcher = Dispatcher::new(env, runner, 0, direct_buffer)?;
+    let start = Instant::now();
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            runtime.block_on(async {
+                for i in 0..events as i64 {
+                    let event = group_message(&client, i % GROUPS);
+                    queue.push(event, status.uin()).await;
+                }
+            });
+            queue.close();
+        });
+        run_shards(env, &dispatcher, &runtime, &queue, &status)
+    })?;
+    Ok(start.elapsed().as_nanos() as jlong)
+}
+
+fn group_message(client: &Arc<ricq::Client>, group_code: i64) -> BotEvent {
+    let mut elements = MessageChain::default();
+    elements.push(elem::Text::new("hello rijq ".repeat(10)));
+    BotEvent::QEvent(QEvent::GroupMessage(GroupMessageEvent {
+        client: client.clone(),
+        inner: GroupMessage {
+            seqs: vec![1],
+            rands: vec![1],
+            group_code,
+            group_name: "benchmark".to_string(),
+            group_card: "rijq".to_string(),
+            from_uin: 10000,
+            time: 0,
+            elements,
+        },
+    }))
+}
@@ -0,0 +1,362 @@
+use anyhow::{anyhow, Context, Result};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::task::Poll;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio::time::sleep;
+
+use crate::event::BotEvent;
+use crate::obj;
+use crate::obj::enums::CaptchaSolverType;
+use crate::session::Bot;
+
+/// 没有配置超时时间时, 滑动条验证最多等待的时间
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 获取滑动条ticket的方式
+#[async_trait::async_trait]
+pub(crate) trait CaptchaSolver: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// 在发出LoginCaptchaEvent之前调用, 事件处理器可能立即提交ticket
+    fn prepare(&self, _bot: &Bot) {}
+
+    /// 返回ticket, 调用方丢弃future即取消
+    async fn solve(&self, bot: &Bot, verify_url: &str) -> Result<String>;
+}
+
+/// 同时运行多个CaptchaSolver, 使用最先得到的ticket
+pub(crate) struct Captcha {
+    solvers: Vec<Box<dyn CaptchaSolver>>,
+    timeout: Duration,
+}
+
+impl Captcha {
+    pub(crate) fn from_config(config: &obj::LoginConfig) -> Result<Self> {
+        let mut types = config
+            .captcha_solvers
+            .iter()
+            .map(|t| {
+                CaptchaSolverType::from_i32(*t)
+                    .with_context(|| format!("未知的滑动条验证方式 : {t}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if types.is_empty() {
+            types = vec![CaptchaSolverType::Java, CaptchaSolverType::TxCaptchaHelper];
+        }
+        let mut solvers: Vec<Box<dyn CaptchaSolver>> = vec![];
+        for t in types {
+            solvers.push(match t {
+                CaptchaSolverType::Java => Box::new(JavaCaptchaSolver::default()),
+                CaptchaSolverType::TxCaptchaHelper => Box::new(TxCaptchaHelper {
+                    interval: Duration::from_secs(5),
+                }),
+                CaptchaSolverType::Callback => Box::new(CallbackCaptchaSolver {
+                    addr: config
+                        .captcha_callback_addr
+                        .parse()
+                        .with_context(|| "滑动条回调地址格式错误")?,
+                }),
+            });
+        }
+        let timeout = if config.captcha_timeout > 0 {
+            Duration::from_secs(config.captcha_timeout as u64)
+        } else {
+            DEFAULT_TIMEOUT
+        };
+        Ok(Self { solvers, timeout })
+    }
+
+    pub(crate) async fn solve(&self, bot: &Bot, verify_url: &str) -> Result<String> {
+        for solver in &self.solvers {
+            solver.prepare(bot);
+        }
+        bot.emit(BotEvent::LoginCaptcha(obj::LoginCaptchaEvent {
+            verify_url: verify_url.to_string(),
+        }))
+        .await;
+        let mut pending = self
+            .solvers
+            .iter()
+            .map(|solver| (solver.name(), solver.solve(bot, verify_url)))
+            .collect::<Vec<_>>();
+        // 某个方式失败时继续等待其他方式, 全部失败才返回错误
+        let any = std::future::poll_fn(|cx| {
+            let mut i = 0;
+            while i < pending.len() {
+                match pending[i].1.as_mut().poll(cx) {
+                    Poll::Ready(Ok(ticket)) => {
+                        tracing::info!("{} 获取到ticket", pending[i].0);
+                        return Poll::Ready(Ok(ticket));
+                    }
+                    Poll::Ready(Err(err)) => {
+                        tracing::warn!("{} 获取ticket失败 : {:?}", pending[i].0, err);
+                        pending.swap_remove(i);
+                    }
+                    Poll::Pending => i += 1,
+                }
+            }
+            if pending.is_empty() {
+                Poll::Ready(Err(anyhow!("所有滑动条验证方式都失败了")))
+            } else {
+                Poll::Pending
+            }
+        });
+        let result = tokio::select! {
+            result = tokio::time::timeout(self.timeout, any) => {
+                result.unwrap_or_else(|_| Err(anyhow!("滑动条验证超时")))
+            }
+            _ = bot.login.cancelled() => Err(anyhow!("滑动条验证已取消")),
+        };
+        // 未完成的solver随future一起丢弃, 清理Java侧的等待
+        bot.login.clear();
+        result
+    }
+}
+
+/// 由Java侧调用SubmitTicket提交ticket
+#[derive(Default)]
+struct JavaCaptchaSolver {
+    ticket: Mutex<Option<oneshot::Receiver<String>>>,
+}
+
+#[async_trait::async_trait]
+impl CaptchaSolver for JavaCaptchaSolver {
+    fn name(&self) -> &'static str {
+        "Java"
+    }
+
+    fn prepare(&self, bot: &Bot) {
+        *self.ticket.lock().unwrap() = Some(bot.login.expect_ticket());
+    }
+
+    async fn solve(&self, _bot: &Bot, _verify_url: &str) -> Result<String> {
+        tracing::info!("可以调用 SubmitTicket 提交滑动条ticket");
+        let ticket = self.ticket.lock().unwrap().take();
+        ticket
+            .with_context(|| "没有等待中的滑动条验证")?
+            .await
+            .with_context(|| "登录等待已取消")
+    }
+}
+
+/// 使用 https://github.com/mzdluo123/TxCaptchaHelper 提供的APP进行滑动, 轮询获取ticket
+struct TxCaptchaHelper {
+    interval: Duration,
+}
+
+#[async_trait::async_trait]
+impl CaptchaSolver for TxCaptchaHelper {
+    fn name(&self) -> &'static str {
+        "TxCaptchaHelper"
+    }
+
+    async fn solve(&self, _bot: &Bot, verify_url: &str) -> Result<String> {
+        let helper_url = verify_url.replace("ssl.captcha.qq.com", "txhelper.glitch.me");
+        tracing::info!("滑动条 (改URL) : {:?}", helper_url);
+        let txt = http_get(&helper_url)
+            .await
+            .with_context(|| "http请求失败")?;
+        tracing::info!("您需要使用该仓库 提供的APP进行滑动 , 滑动后请等待, https://github.com/mzdluo123/TxCaptchaHelper : {}", txt);
+        loop {
+            sleep(self.interval).await;
+            let rsp = http_get(&helper_url)
+                .await
+                .with_context(|| "http请求失败")?;
+            if !rsp.eq(&txt) {
+                return Ok(rsp);
+            }
+        }
+    }
+}
+
+/// 在本地监听HTTP, 接收 GET /?ticket=xxx 或者body为ticket的POST请求
+struct CallbackCaptchaSolver {
+    addr: SocketAddr,
+}
+
+#[async_trait::async_trait]
+impl CaptchaSolver for CallbackCaptchaSolver {
+    fn name(&self) -> &'static str {
+        "Callback"
+    }
+
+    async fn solve(&self, _bot: &Bot, _verify_url: &str) -> Result<String> {
+        let listener = TcpListener::bind(self.addr)
+            .await
+            .with_context(|| format!("滑动条回调地址监听失败 : {}", self.addr))?;
+        tracing::info!("滑动条完成后请将ticket提交到 http://{}/?ticket=", self.addr);
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            match receive_ticket(stream).await {
+                Ok(Some(ticket)) => return Ok(ticket),
+                Ok(None) => tracing::warn!("滑动条回调请求中没有ticket : {}", peer),
+                Err(err) => tracing::warn!("滑动条回调请求处理失败 : {} {:?}", peer, err),
+            }
+        }
+    }
+}
+
+async fn receive_ticket(mut stream: TcpStream) -> Result<Option<String>> {
+    let mut buff = vec![0u8; 8192];
+    let mut len = 0;
+    // 读取到请求头结束, 请求体只取已经读到的部分
+    let header_end = loop {
+        if len == buff.len() {
+            return Err(anyhow!("请求过大"));
+        }
+        let read = tokio::time::timeout(Duration::from_secs(10), stream.read(&mut buff[len..]))
+            .await
+            .with_context(|| "读取请求超时")??;
+        if read == 0 {
+            return Err(anyhow!("连接已关闭"));
+        }
+        len += read;
+        if let Some(pos) = buff[..len].windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+    };
+    let head = String::from_utf8_lossy(&buff[..header_end]).to_string();
+    let body = String::from_utf8_lossy(&buff[header_end + 4..len]).to_string();
+    let ticket = parse_ticket(&head, &body);
+    let response = match ticket {
+        Some(_) => "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nOK",
+        None => {
+            "HTTP/1.1 400 Bad Request\r\nContent-Length: 9\r\nConnection: close\r\n\r\nno ticket"
+        }
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(ticket)
+}
+
+/// 从请求头和已经读到的请求体中取出ticket
+fn parse_ticket(head: &str, body: &str) -> Option<String> {
+    let mut lines = head.lines();
+    let target = lines
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .unwrap_or_default();
+    // 只有表单编码的请求体中+表示空格, 其他位置的+是ticket的一部分
+    let form = lines
+        .filter_map(|line| line.split_once(':'))
+        .any(|(key, value)| {
+            key.trim().eq_ignore_ascii_case("content-type")
+                && value
+                    .trim()
+                    .to_ascii_lowercase()
+                    .starts_with("application/x-www-form-urlencoded")
+        });
+    let body = body.trim();
+    target
+        .split_once('?')
+        .and_then(|(_, query)| query_param(query, "ticket", false))
+        .or_else(|| query_param(body, "ticket", form))
+        .or_else(|| (!body.is_empty() && !body.contains('=')).then(|| body.to_string()))
+}
+
+fn query_param(query: &str, name: &str, form: bool) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| percent_decode(value, form))
+        .filter(|value| !value.is_empty())
+}
+
+/// 不完整或者不是hex的%转义原样保留, plus_as_space只用于表单编码的内容
+fn percent_decode(value: &str, plus_as_space: bool) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                // from_str_radix会接受+和-, 需要先检查
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(b) => {
+                        out.push(b);
+                        i += 3;
+                        continue;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b'+' if plus_as_space => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+async fn http_get(url: &str) -> Result<String> {
+    Ok(reqwest::ClientBuilder::new().build()?.get(url).header(
+        "user-agent", "Mozilla/5.0 (Linux; Android 6.0; Nexus 5 Build/MRA58N) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/98.0.4758.80 Mobile Safari/537.36",
+    ).send().await?
+        .text()
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decode_escapes() {
+        assert_eq!(percent_decode("t03%2Ba%2fb", false), "t03+a/b");
+        assert_eq!(percent_decode("a+b", false), "a+b");
+        assert_eq!(percent_decode("a+b%2B", true), "a b+");
+        // 不完整或者不是hex的转义原样保留
+        for (malformed, expected) in [
+            ("%", "%"),
+            ("%4", "%4"),
+            ("a%zz", "a%zz"),
+            ("%+1", "%+1"),
+            ("%-1", "%-1"),
+            ("%%41", "%A"),
+        ] {
+            assert_eq!(percent_decode(malformed, false), expected);
+        }
+        assert_eq!(percent_decode("%e4%bd%a0", false), "你");
+    }
+
+    #[test]
+    fn query_params() {
+        assert_eq!(
+            query_param("a=1&ticket=t03+x%2By", "ticket", false).as_deref(),
+            Some("t03+x+y")
+        );
+        assert_eq!(
+            query_param("ticket=t03+x", "ticket", true).as_deref(),
+            Some("t03 x")
+        );
+        assert_eq!(query_param("ticket=", "ticket", false), None);
+        assert_eq!(query_param("ticket", "ticket", false), None);
+        assert_eq!(query_param("tickets=1", "ticket", false), None);
+    }
+
+    #[test]
+    fn parse_tickets() {
+        let get = "GET /?ticket=t03+a%2B HTTP/1.1\r\nHost: localhost";
+        assert_eq!(parse_ticket(get, "").as_deref(), Some("t03+a+"));
+        let form = "POST / HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded";
+        assert_eq!(
+            parse_ticket(form, "ticket=t03+a%2B").as_deref(),
+            Some("t03 a+")
+        );
+        let raw = "POST / HTTP/1.1\r\nContent-Type: text/plain";
+        assert_eq!(parse_ticket(raw, " t03+a \r\n").as_deref(), Some("t03+a"));
+        assert_eq!(parse_ticket(raw, "ticket=t03+a").as_deref(), Some("t03+a"));
+        assert_eq!(parse_ticket("GET / HTTP/1.1", ""), None);
+        assert_eq!(parse_ticket("GET /?a=1 HTTP/1.1", "b=2"), None);
+    }
+}
@@ -0,0 +1,148 @@
+use anyhow::{anyhow, Context, Result};
+use prost::Message;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tracing::metadata::LevelFilter;
+
+use crate::captcha::Captcha;
+use crate::device;
+use crate::obj;
+use crate::obj::enums::{LoginMethod, OverflowStrategy, SessionStoreType};
+
+const MAX_DISPATCH_THREADS: i32 = 64;
+
+/// 解析并校验Java传入的配置, 任何错误都在连接服务器之前返回
+pub(crate) fn decode(data: &[u8]) -> Result<obj::RijqConfig> {
+    let config = obj::RijqConfig::decode(data).context("parse RijqConfig error")?;
+    validate(&config).context("RijqConfig 校验失败")?;
+    Ok(config)
+}
+
+fn validate(config: &obj::RijqConfig) -> Result<()> {
+    validate_login(config.login.as_ref().with_context(|| "缺少登录配置")?)?;
+    device::protocol(&config.device.clone().unwrap_or_default())?;
+    let store = config.store.clone().unwrap_or_default();
+    if SessionStoreType::from_i32(store.session_store).is_none() {
+        return Err(anyhow!("未知的会话存储方式 : {}", store.session_store));
+    }
+    if !store.store_key.is_empty() && store.store_key.len() != 32 {
+        return Err(anyhow!("加密密钥必须为32字节"));
+    }
+    let runtime = runtime_config(config);
+    if runtime.worker_threads <= 0 || runtime.max_blocking_threads <= 0 {
+        return Err(anyhow!("worker_threads和max_blocking_threads必须大于0"));
+    }
+    if runtime.thread_keep_alive < 0 {
+        return Err(anyhow!("thread_keep_alive不能为负数"));
+    }
+    let reconnect = reconnect_config(config);
+    if reconnect.max_attempts < 0 {
+        return Err(anyhow!("max_attempts不能为负数"));
+    }
+    if reconnect.initial_delay <= 0 || reconnect.max_delay < reconnect.initial_delay {
+        return Err(anyhow!("重连间隔需要满足 0 < initial_delay <= max_delay"));
+    }
+    if reconnect.multiplier < 1.0 {
+        return Err(anyhow!("multiplier不能小于1"));
+    }
+    if !(0.0..=1.0).contains(&reconnect.jitter) {
+        return Err(anyhow!("jitter需要在0和1之间"));
+    }
+    if reconnect.blacklist_duration < 0 {
+        return Err(anyhow!("blacklist_duration不能为负数"));
+    }
+    let queue = event_queue_config(config);
+    if queue.capacity <= 0 {
+        return Err(anyhow!("事件队列容量必须大于0"));
+    }
+    match OverflowStrategy::from_i32(queue.overflow) {
+        Some(OverflowStrategy::DropByType) if queue.droppable_events.is_empty() => {
+            return Err(anyhow!("DropByType需要配置可以丢弃的事件类型"));
+        }
+        Some(_) => {}
+        None => return Err(anyhow!("未知的事件队列溢出策略 : {}", queue.overflow)),
+    }
+    let dispatch = dispatch_config(config);
+    if dispatch.threads <= 0 || dispatch.threads > MAX_DISPATCH_THREADS {
+        return Err(anyhow!("分发线程数需要在1和{}之间", MAX_DISPATCH_THREADS));
+    }
+    log_level(config)?;
+    Ok(())
+}
+
+fn validate_login(config: &obj::LoginConfig) -> Result<()> {
+    Captcha::from_config(config)?;
+    match LoginMethod::from_i32(config.method) {
+        Some(LoginMethod::QrCode) => Ok(()),
+        Some(LoginMethod::Password) => {
+            if config.uin == 0 || config.password.is_empty() {
+                return Err(anyhow!("密码登录需要配置uin和password"));
+            }
+            Ok(())
+        }
+        Some(LoginMethod::PasswordMd5) => {
+            if config.uin == 0 || config.password_md5.len() != 16 {
+                return Err(anyhow!("MD5密码登录需要配置uin和16字节的password_md5"));
+            }
+            Ok(())
+        }
+        None => Err(anyhow!("未知的登录方式 : {}", config.method)),
+    }
+}
+
+/// 没有配置时使用10个工作线程, 10个阻塞线程, 线程空闲100秒
+pub(crate) fn runtime_config(config: &obj::RijqConfig) -> obj::RuntimeConfig {
+    config.runtime.clone().unwrap_or(obj::RuntimeConfig {
+        worker_threads: 10,
+        max_blocking_threads: 10,
+        thread_keep_alive: 100,
+    })
+}
+
+/// 没有配置时不限制次数, 间隔从1秒开始翻倍, 最多60秒, 失败的地址1分钟内不再使用
+pub(crate) fn reconnect_config(config: &obj::RijqConfig) -> obj::ReconnectConfig {
+    config.reconnect.clone().unwrap_or(obj::ReconnectConfig {
+        max_attempts: 0,
+        initial_delay: 1000,
+        max_delay: 60000,
+        multiplier: 2.0,
+        jitter: 0.2,
+        blacklist_duration: 60000,
+    })
+}
+
+/// 没有配置时最多缓存10000个事件, 队列满时阻塞ricq
+pub(crate) fn event_queue_config(config: &obj::RijqConfig) -> obj::EventQueueConfig {
+    config.event_queue.clone().unwrap_or(obj::EventQueueConfig {
+        capacity: 10000,
+        overflow: OverflowStrategy::Block as i32,
+        droppable_events: vec![],
+    })
+}
+
+/// 没有配置时只在daemon线程上分发事件
+pub(crate) fn dispatch_config(config: &obj::RijqConfig) -> obj::DispatchConfig {
+    config.dispatch.clone().unwrap_or(obj::DispatchConfig {
+        threads: 1,
+        direct_buffer: false,
+    })
+}
+
+pub(crate) fn log_level(config: &obj::RijqConfig) -> Result<LevelFilter> {
+    if config.log_level.is_empty() {
+        return Ok(LevelFilter::INFO);
+    }
+    LevelFilter::from_str(config.log_level.as_str())
+        .map_err(|_| anyhow!("未知的日志级别 : {}", config.log_level))
+}
+
+pub(crate) fn build_runtime(config: &obj::RijqConfig) -> Result<Runtime> {
+    let runtime = runtime_config(config);
+    Ok(tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_keep_alive(Duration::from_secs(runtime.thread_keep_alive as u64))
+        .worker_threads(runtime.worker_threads as usize)
+        .max_blocking_threads(runtime.max_blocking_threads as usize)
+        .build()?)
+}
@@ -0,0 +1,207 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, Context, Result};
+
+use crate::obj;
+
+/// 没有从Java传入密钥时, 从该环境变量读取hex格式的密钥
+const KEY_ENV: &str = "RIJQ_STORE_KEY";
+
+/// 加密文件的格式: MAGIC(7) + VERSION(1) + nonce(12) + 密文和tag
+const MAGIC: &[u8; 7] = b"RIJQENC";
+const VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 1 + NONCE_LEN;
+
+/// 加密会话和设备文件使用的AES-256-GCM
+pub(crate) struct StoreCipher {
+    cipher: Aes256Gcm,
+}
+
+impl StoreCipher {
+    /// 优先使用Java传入的密钥, 其次是环境变量, 都没有时不加密
+    pub(crate) fn from_config(config: &obj::StoreConfig) -> Result<Option<Self>> {
+        let key = if !config.store_key.is_empty() {
+            config.store_key.clone()
+        } else {
+            match std::env::var(KEY_ENV) {
+                Ok(hex) if !hex.trim().is_empty() => {
+                    decode_hex(hex.trim()).with_context(|| format!("{KEY_ENV} 不是hex格式"))?
+                }
+                _ => return Ok(None),
+            }
+        };
+        let cipher = Aes256Gcm::new_from_slice(key.as_slice())
+            .map_err(|_| anyhow!("加密密钥必须为32字节, 实际为 {} 字节", key.len()))?;
+        Ok(Some(Self { cipher }))
+    }
+
+    pub(crate) fn seal(&self, plain: &[u8]) -> Result<Vec<u8>> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let encrypted = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plain)
+            .map_err(|_| anyhow!("加密失败"))?;
+        let mut data = Vec::with_capacity(HEADER_LEN + encrypted.len());
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&encrypted);
+        Ok(data)
+    }
+
+    fn open(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < HEADER_LEN {
+            return Err(anyhow!("加密文件已损坏"));
+        }
+        let version = data[MAGIC.len()];
+        if version != VERSION {
+            return Err(anyhow!("不支持的加密文件版本 : {}", version));
+        }
+        let nonce = &data[MAGIC.len() + 1..HEADER_LEN];
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), &data[HEADER_LEN..])
+            .map_err(|_| anyhow!("解密失败, 密钥错误或文件已损坏"))
+    }
+}
+
+/// 文件内容的读取结果
+pub(crate) enum Opened {
+    /// 已加密的文件, 或者没有配置密钥时的明文文件
+    Current(Vec<u8>),
+    /// 配置了密钥但文件仍为明文, 需要重新加密保存
+    Legacy(Vec<u8>),
+}
+
+/// 读取可能加密的文件内容, 明文文件原样返回
+pub(crate) fn open(cipher: Option<&StoreCipher>, data: Vec<u8>) -> Result<Opened> {
+    if !data.starts_with(MAGIC) {
+        return match cipher {
+            Some(_) => Ok(Opened::Legacy(data)),
+            None => Ok(Opened::Current(data)),
+        };
+    }
+    match cipher {
+        Some(cipher) => Ok(Opened::Current(cipher.open(data.as_slice())?)),
+        None => Err(anyhow!("文件已加密, 请配置密钥")),
+    }
+}
+
+/// 配置了密钥时加密, 否则原样返回
+pub(crate) fn seal(cipher: Option<&StoreCipher>, data: Vec<u8>) -> Result<Vec<u8>> {
+    match cipher {
+        Some(cipher) => cipher.seal(data.as_slice()),
+        None => Ok(data),
+    }
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    if hex.len() % 2 == 1 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(anyhow!("hex格式错误"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(Into::into))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(key: u8) -> StoreCipher {
+        let config = obj::StoreConfig {
+            store_key: vec![key; 32],
+            ..Default::default()
+        };
+        StoreCipher::from_config(&config).unwrap().unwrap()
+    }
+
+    fn current(opened: Opened) -> Vec<u8> {
+        match opened {
+            Opened::Current(data) => data,
+            Opened::Legacy(_) => panic!("expected current"),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let cipher = cipher(1);
+        let plain = b"rijq session".to_vec();
+        let sealed = seal(Some(&cipher), plain.clone()).unwrap();
+        assert!(sealed.starts_with(MAGIC));
+        assert_eq!(sealed[MAGIC.len()], VERSION);
+        // 每次使用新的nonce
+        assert_ne!(sealed, seal(Some(&cipher), plain.clone()).unwrap());
+        assert_eq!(current(open(Some(&cipher), sealed).unwrap()), plain);
+    }
+
+    #[test]
+    fn wrong_key() {
+        let sealed = seal(Some(&cipher(1)), b"rijq".to_vec()).unwrap();
+        assert!(open(Some(&cipher(2)), sealed).is_err());
+    }
+
+    #[test]
+    fn wrong_key_length() {
+        let config = obj::StoreConfig {
+            store_key: vec![1; 16],
+            ..Default::default()
+        };
+        assert!(StoreCipher::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        let cipher = cipher(1);
+        let sealed = seal(Some(&cipher), b"rijq".to_vec()).unwrap();
+        for len in MAGIC.len()..sealed.len() {
+            assert!(
+                open(Some(&cipher), sealed[..len].to_vec()).is_err(),
+                "len {len}"
+            );
+        }
+    }
+
+    #[test]
+    fn corrupted() {
+        let cipher = cipher(1);
+        let sealed = seal(Some(&cipher), b"rijq".to_vec()).unwrap();
+        for index in MAGIC.len() + 1..sealed.len() {
+            let mut data = sealed.clone();
+            data[index] ^= 1;
+            assert!(open(Some(&cipher), data).is_err(), "index {index}");
+        }
+    }
+
+    #[test]
+    fn unknown_version() {
+        let cipher = cipher(1);
+        let mut sealed = seal(Some(&cipher), b"rijq".to_vec()).unwrap();
+        sealed[MAGIC.len()] = VERSION + 1;
+        let err = open(Some(&cipher), sealed).err().unwrap();
+        assert!(err.to_string().contains("版本"), "{err}");
+    }
+
+    #[test]
+    fn legacy_plain_text() {
+        let plain = b"plain session".to_vec();
+        match open(Some(&cipher(1)), plain.clone()).unwrap() {
+            Opened::Legacy(data) => assert_eq!(data, plain),
+            Opened::Current(_) => panic!("expected legacy"),
+        }
+        // 没有配置密钥时明文就是当前格式
+        assert_eq!(current(open(None, plain.clone()).unwrap()), plain);
+        let sealed = seal(Some(&cipher(1)), plain).unwrap();
+        assert!(open(None, sealed).is_err());
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(decode_hex("").unwrap(), Vec::<u8>::new());
+        assert_eq!(decode_hex("00aFff").unwrap(), vec![0x00, 0xaf, 0xff]);
+        for bad in ["a", "abc", "zz", "+1", "-1", " 1", "é1"] {
+            assert!(decode_hex(bad).is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{anyhow, Context, Result};
+use ricq::version::{Version, ANDROID_PAD, ANDROID_PHONE, ANDROID_WATCH, IPAD, MACOS};
+use ricq_core::protocol::device::Device;
+use std::path::Path;
+
+use crate::crypto::{self, Opened, StoreCipher};
+use crate::obj;
+use crate::obj::enums::Protocol;
+
+pub(crate) fn protocol(config: &obj::DeviceConfig) -> Result<Version> {
+    match Protocol::from_i32(config.protocol) {
+        Some(Protocol::AndroidWatch) => Ok(ANDROID_WATCH),
+        Some(Protocol::AndroidPhone) => Ok(ANDROID_PHONE),
+        Some(Protocol::AndroidPad) => Ok(ANDROID_PAD),
+        Some(Protocol::IPad) => Ok(IPAD),
+        Some(Protocol::MacOs) => Ok(MACOS),
+        None => Err(anyhow!("未知的协议 : {}", config.protocol)),
+    }
+}
+
+/// 读取设备文件, 不存在时随机生成, 配置中非空的设备字段会覆盖文件中的值并保存
+pub(crate) async fn device(
+    config: &obj::DeviceConfig,
+    cipher: Option<&StoreCipher>,
+) -> Result<Device> {
+    let file_name = if config.device_path.is_empty() {
+        "device.json"
+    } else {
+        config.device_path.as_str()
+    };
+    let (device, mut changed) = if Path::new(file_name).exists() {
+        match crypto::open(cipher, tokio::fs::read(file_name).await?)? {
+            Opened::Current(data) => (serde_json::from_slice(&data)?, false),
+            Opened::Legacy(data) => {
+                tracing::info!("设备信息为明文, 重新加密保存");
+                (serde_json::from_slice(&data)?, true)
+            }
+        }
+    } else {
+        (Device::random(), true)
+    };
+    let device = match config.device_info {
+        Some(ref info) => {
+            let (device, overridden) = apply_device_info(device, info)?;
+            changed |= overridden;
+            device
+        }
+        None => device,
+    };
+    if changed {
+        let data = crypto::seal(cipher, serde_json::to_vec(&device)?)?;
+        tokio::fs::write(file_name, data)
+            .await
+            .with_context(|| format!("保存设备文件失败 : {file_name}"))?;
+    }
+    Ok(device)
+}
+
+/// 按照device.json中的字段名覆盖, 返回是否有字段被修改
+fn apply_device_info(device: Device, info: &obj::DeviceInfo) -> Result<(Device, bool)> {
+    let mut value = serde_json::to_value(&device)?;
+    let fields = value.as_object_mut().with_context(|| "设备信息格式错误")?;
+    let mut changed = false;
+    for (name, field) in [
+        ("display", &info.display),
+        ("product", &info.product),
+        ("device", &info.device),
+        ("board", &info.board),
+        ("model", &info.model),
+        ("finger_print", &info.finger_print),
+        ("boot_id", &info.boot_id),
+        ("proc_version", &info.proc_version),
+        ("imei", &info.imei),
+        ("brand", &info.brand),
+        ("bootloader", &info.bootloader),
+        ("base_band", &info.base_band),
+        ("sim_info", &info.sim_info),
+        ("os_type", &info.os_type),
+        ("mac_address", &info.mac_address),
+        ("wifi_bssid", &info.wifi_bssid),
+        ("wifi_ssid", &info.wifi_ssid),
+        ("android_id", &info.android_id),
+        ("apn", &info.apn),
+        ("vendor_name", &info.vendor_name),
+        ("vendor_os_name", &info.vendor_os_name),
+    ] {
+        if field.is_empty() {
+            continue;
+        }
+        let field = serde_json::Value::String(field.clone());
+        if fields.get(name) != Some(&field) {
+            fields.insert(name.to_string(), field);
+            changed = true;
+        }
+    }
+    Ok((serde_json::from_value(value)?, changed))
+}
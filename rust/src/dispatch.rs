@@ -0,0 +1,273 @@
+use anyhow::{anyhow, Result};
+use jni::objects::{GlobalRef, JMethodID, JObject, JValue};
+use jni::signature::{Primitive, ReturnType};
+use jni::sys::jvalue;
+use jni::{JNIEnv, JavaVM};
+use tokio::runtime::Runtime;
+
+use crate::jni_cache::{self, JniCache};
+use crate::obj;
+use crate::queue::{EventQueue, QueuedEvent};
+use crate::session::Session;
+use crate::status::Status;
+
+/// 把事件分发给InitRunner, 直到机器人停止
+pub(crate) fn dispatch_events(
+    env: &mut JNIEnv,
+    runner: &JObject,
+    handle: i64,
+    session: &Session,
+    config: &obj::DispatchConfig,
+) -> Result<()> {
+    // 把句柄传递给InitRunner
+    env.call_method(runner, "setHandle", "(J)V", &[JValue::Long(handle)])?;
+    let dispatcher = Dispatcher::new(env, runner, handle, config.direct_buffer)?;
+    tracing::info!("dispatch threads : {}", session.bot.events.shards());
+    run_shards(
+        env,
+        &dispatcher,
+        &session.runtime,
+        &session.bot.events,
+        &session.bot.status,
+    )
+}
+
+/// 第0个分片在当前线程上分发, 其他分片各使用一个附加到JVM的线程
+///
+/// 同一会话的事件在同一个分片中按顺序分发, 不同会话的事件可以并行分发。
+/// 任何分片出错时都关闭队列, 所有分片分发完已经在队列中的事件后返回, 之后daemon停止机器人
+pub(crate) fn run_shards(
+    env: &mut JNIEnv,
+    dispatcher: &Dispatcher,
+    runtime: &Runtime,
+    events: &EventQueue,
+    status: &Status,
+) -> Result<()> {
+    let vm = env.get_java_vm()?;
+    std::thread::scope(|scope| {
+        let mut result = Ok(());
+        // 没有线程分发的分片, 由当前线程在第0个分片之后分发
+        let mut orphans = vec![];
+        let mut threads = vec![];
+        for shard in 1..events.shards() {
+            let vm = &vm;
+            let spawned = std::thread::Builder::new()
+                .name(format!("rijq-dispatch-{}-{shard}", dispatcher.handle))
+                .spawn_scoped(scope, move || {
+                    dispatch_on_attached(vm, dispatcher, runtime, events, status, shard)
+                });
+            match spawned {
+                Ok(thread) => threads.push((shard, thread)),
+                Err(err) => {
+                    tracing::error!("spawn dispatch thread error : {:?}", err);
+                    events.close();
+                    keep_first(&mut result, err.into());
+                    orphans.push(shard);
+                }
+            }
+        }
+        for shard in std::iter::once(0).chain(orphans) {
+            if let Err(err) = run_shard(env, dispatcher, runtime, events, status, shard) {
+                keep_first(&mut result, err);
+            }
+        }
+        for (shard, thread) in threads {
+            let exit = thread.join().unwrap_or_else(|_| {
+                ShardExit::Finished(Err(anyhow!("dispatch thread {shard} panicked")))
+            });
+            match exit {
+                ShardExit::Finished(Ok(())) => {}
+                ShardExit::Finished(Err(err)) => keep_first(&mut result, err),
+                // 分片中的事件由当前线程分发
+                ShardExit::AttachFailed(err) => {
+                    keep_first(&mut result, err);
+                    if let Err(err) = run_shard(env, dispatcher, runtime, events, status, shard) {
+                        keep_first(&mut result, err);
+                    }
+                }
+            }
+        }
+        result
+    })
+}
+
+enum ShardExit {
+    Finished(Result<()>),
+    /// 未能附加到JVM, 还没有取出事件
+    AttachFailed(anyhow::Error),
+}
+
+fn dispatch_on_attached(
+    vm: &JavaVM,
+    dispatcher: &Dispatcher,
+    runtime: &Runtime,
+    events: &EventQueue,
+    status: &Status,
+    shard: usize,
+) -> ShardExit {
+    match vm.attach_current_thread_as_daemon() {
+        Ok(mut env) => ShardExit::Finished(run_shard(
+            &mut env, dispatcher, runtime, events, status, shard,
+        )),
+        Err(err) => {
+            tracing::error!("attach thread error : {:?}", err);
+            events.close();
+            ShardExit::AttachFailed(err.into())
+        }
+    }
+}
+
+fn run_shard(
+    env: &mut JNIEnv,
+    dispatcher: &Dispatcher,
+    runtime: &Runtime,
+    events: &EventQueue,
+    status: &Status,
+    shard: usize,
+) -> Result<()> {
+    drain_shard(events, || {
+        dispatcher.run(env, runtime, events, status, shard)
+    })
+}
+
+/// 出错时记录错误并关闭队列, 继续分发分片中剩余的事件, 返回第一个错误
+fn drain_shard(events: &EventQueue, mut run: impl FnMut() -> Result<()>) -> Result<()> {
+    let mut result = Ok(());
+    while let Err(err) = run() {
+        tracing::error!("dispatch event error : {:?}", err);
+        events.close();
+        keep_first(&mut result, err);
+    }
+    result
+}
+
+fn keep_first(result: &mut Result<()>, err: anyhow::Error) {
+    if result.is_ok() {
+        *result = Err(err);
+    }
+}
+
+pub(crate) struct Dispatcher {
+    runner: GlobalRef,
+    dispatch_method: JMethodID,
+    handle: i64,
+    cache: &'static JniCache,
+    direct_buffer: bool,
+}
+
+impl Dispatcher {
+    /// runner需要有 dispatchEventMethodPoint(long handle, long botUin, Object event) 方法
+    pub(crate) fn new(
+        env: &mut JNIEnv,
+        runner: &JObject,
+        handle: i64,
+        direct_buffer: bool,
+    ) -> Result<Self> {
+        let runner_class = env.get_object_class(runner)?;
+        tracing::info!("got runner class");
+        let dispatch_method = env.get_method_id(
+            &runner_class,
+            "dispatchEventMethodPoint",
+            "(JJLjava/lang/Object;)V",
+        )?;
+        Ok(Self {
+            runner: env.new_global_ref(runner)?,
+            dispatch_method,
+            handle,
+            cache: jni_cache::get()?,
+            direct_buffer,
+        })
+    }
+
+    /// 开始接收事件, 机器人停止后分发完分片中剩余的事件再返回
+    fn run(
+        &self,
+        env: &mut JNIEnv,
+        runtime: &Runtime,
+        events: &EventQueue,
+        status: &Status,
+        shard: usize,
+    ) -> Result<()> {
+        while let Some(event) = runtime.block_on(events.pop(shard)) {
+            // 事件标记接收的机器人, 扫码登录完成前为0
+            if let Err(err) = self.dispatch(env, status.uin(), event) {
+                let _ = env.exception_describe();
+                let _ = env.exception_clear();
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    fn dispatch(&self, env: &mut JNIEnv, bot_uin: i64, event: QueuedEvent) -> Result<()> {
+        let QueuedEvent { name, mut data, .. } = event;
+        let event_class = self.cache.event(name)?;
+        let dispatched = env.with_local_frame(8, |env| -> jni::errors::Result<()> {
+            let de = if self.direct_buffer {
+                event_class.parse_direct(env, &mut data)?
+            } else {
+                event_class.parse_bytes(env, &data)?
+            };
+            unsafe {
+                env.call_method_unchecked(
+                    &self.runner,
+                    self.dispatch_method,
+                    ReturnType::Primitive(Primitive::Void),
+                    &[
+                        jvalue { j: self.handle },
+                        jvalue { j: bot_uin },
+                        jvalue { l: de.as_raw() },
+                    ],
+                )?;
+            }
+            Ok(())
+        });
+        match dispatched {
+            Ok(_) => {}
+            // 事件处理器抛出的异常不应结束事件循环
+            Err(jni::errors::Error::JavaException) => {
+                env.exception_describe()?;
+                env.exception_clear()?;
+            }
+            Err(err) => return Err(err.into()),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obj::enums::OverflowStrategy;
+
+    #[test]
+    fn error_closes_queue_and_drains_shard() {
+        let runtime = Runtime::new().unwrap();
+        let events = EventQueue::new(
+            &obj::EventQueueConfig {
+                capacity: 10,
+                overflow: OverflowStrategy::Block as i32,
+                droppable_events: vec![],
+            },
+            1,
+        );
+        for data in 1..=3 {
+            runtime.block_on(events.push_mapped("A", vec![data], 0));
+        }
+        let mut delivered = vec![];
+        // 队列没有关闭时第二次调用会一直等待新事件
+        let result = drain_shard(&events, || {
+            while let Some(event) = runtime.block_on(events.pop(0)) {
+                if event.data[0] == 1 {
+                    return Err(anyhow!("dispatch failed"));
+                }
+                delivered.push(event.data[0]);
+            }
+            Ok(())
+        });
+        assert_eq!(result.unwrap_err().to_string(), "dispatch failed");
+        assert_eq!(delivered, [2, 3]);
+        runtime.block_on(events.push_mapped("A", vec![4], 0));
+        assert_eq!(events.len(), 0);
+    }
+}
@@ -0,0 +1,108 @@
+use jni::JNIEnv;
+use ricq::RQError;
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::RefCell;
+use std::panic::AssertUnwindSafe;
+use std::sync::Once;
+
+use crate::obj::enums::ErrorType;
+
+const NATIVE_EXCEPTION_CLASS: &str = "rijq/framework/RijqNativeException";
+
+/// callNative失败时返回给Java的错误, error_type供Java区分错误原因
+pub(crate) struct NativeError {
+    pub error_type: ErrorType,
+    pub message: String,
+}
+
+impl NativeError {
+    pub(crate) fn new(error_type: ErrorType, message: impl Into<String>) -> Self {
+        Self {
+            error_type,
+            message: message.into(),
+        }
+    }
+}
+
+/// 为错误加上调用上下文, 用法同anyhow::Context
+pub(crate) trait NativeResultExt<T> {
+    fn context(self, context: &str) -> Result<T, NativeError>;
+}
+
+impl<T, E> NativeResultExt<T> for Result<T, E>
+where
+    E: Into<NativeError>,
+{
+    fn context(self, context: &str) -> Result<T, NativeError> {
+        self.map_err(|err| {
+            let err = err.into();
+            NativeError::new(err.error_type, format!("{context}. {}", err.message))
+        })
+    }
+}
+
+impl From<RQError> for NativeError {
+    fn from(err: RQError) -> Self {
+        let error_type = match err {
+            RQError::Timeout => ErrorType::Timeout,
+            RQError::Network | RQError::IO(_) => ErrorType::Network,
+            _ => ErrorType::Remote,
+        };
+        Self::new(error_type, err.to_string())
+    }
+}
+
+thread_local! {
+    // panic hook记录的panic信息和backtrace, 由catch_jni取出
+    static LAST_PANIC: RefCell<Option<String>> = RefCell::new(None);
+}
+
+static PANIC_HOOK: Once = Once::new();
+
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            // 遵循RUST_BACKTRACE, 未开启时只记录panic信息
+            let backtrace = Backtrace::capture();
+            let message = match backtrace.status() {
+                BacktraceStatus::Captured => format!("{info}\n{backtrace}"),
+                _ => info.to_string(),
+            };
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(message));
+            default_hook(info);
+        }));
+    });
+}
+
+/// 在JNI边界执行f, 将panic和错误转换为Java异常抛出, 此时返回fallback
+pub(crate) fn catch_jni<T, F>(env: &mut JNIEnv, fallback: T, f: F) -> T
+where
+    F: FnOnce(&mut JNIEnv) -> anyhow::Result<T>,
+{
+    install_panic_hook();
+    let message = match std::panic::catch_unwind(AssertUnwindSafe(|| f(env))) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(err)) => format!("{err:?}"),
+        Err(payload) => LAST_PANIC
+            .with(|last| last.borrow_mut().take())
+            .unwrap_or_else(|| panic_message(payload.as_ref())),
+    };
+    tracing::error!("native error : {message}");
+    // 已经有待抛出的Java异常时(例如事件处理器抛出), 保留原异常
+    if !env.exception_check().unwrap_or(false) {
+        let _ = env.throw_new(NATIVE_EXCEPTION_CLASS, message);
+    }
+    fallback
+}
+
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "rust panic".to_string()
+    }
+}
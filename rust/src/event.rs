@@ -0,0 +1,341 @@
+use prost::Message;
+use ricq::handler::QEvent;
+use ricq_core::structs::GroupMemberPermission;
+
+use crate::{map_elements, obj};
+
+/// 传递给Java的事件, 除了ricq产生的事件之外还有登录流程中的事件
+#[derive(Debug)]
+pub(crate) enum BotEvent {
+    QEvent(QEvent),
+    LoginQrCode(obj::LoginQrCodeEvent),
+    LoginCaptcha(obj::LoginCaptchaEvent),
+    LoginDeviceLock(obj::LoginDeviceLockEvent),
+    Disconnected(obj::DisconnectedEvent),
+    Reconnecting(obj::ReconnectingEvent),
+    Reconnected(obj::ReconnectedEvent),
+    SessionLost(obj::SessionLostEvent),
+}
+
+/// map_event可能返回的Java事件类名, JNI_OnLoad时加载这些类
+pub(crate) const EVENT_CLASSES: &[&str] = &[
+    "LoginEvent",
+    "LoginQrCodeEvent",
+    "LoginCaptchaEvent",
+    "LoginDeviceLockEvent",
+    "DisconnectedEvent",
+    "ReconnectingEvent",
+    "ReconnectedEvent",
+    "SessionLostEvent",
+    "GroupMessageEvent",
+    "FriendMessageEvent",
+    "GroupTempMessageEvent",
+    "GroupRequestEvent",
+    "SelfInvitedEvent",
+    "NewFriendRequestEvent",
+    "NewMemberEvent",
+    "GroupMuteEvent",
+    "FriendMessageRecallEvent",
+    "GroupMessageRecallEvent",
+    "NewFriendEvent",
+    "GroupLeaveEvent",
+    "GroupDisbandEvent",
+    "FriendPokeEvent",
+    "GroupPokeEvent",
+    "GroupNameUpdateEvent",
+    "DeleteFriendEvent",
+    "MemberPermissionChangeEvent",
+    "KickedOfflineEvent",
+    "MSFOfflineEvent",
+    "ClientDisconnectEvent",
+];
+
+/// 将事件转换为Java事件类名和protobuf数据, 不需要传递给Java的事件返回None
+pub(crate) fn map_event(event: BotEvent) -> Option<(&'static str, Vec<u8>)> {
+    match event {
+        BotEvent::QEvent(event) => map_q_event(event),
+        BotEvent::LoginQrCode(event) => encode("LoginQrCodeEvent", event),
+        BotEvent::LoginCaptcha(event) => encode("LoginCaptchaEvent", event),
+        BotEvent::LoginDeviceLock(event) => encode("LoginDeviceLockEvent", event),
+        BotEvent::Disconnected(event) => encode("DisconnectedEvent", event),
+        BotEvent::Reconnecting(event) => encode("ReconnectingEvent", event),
+        BotEvent::Reconnected(event) => encode("ReconnectedEvent", event),
+        BotEvent::SessionLost(event) => encode("SessionLostEvent", event),
+    }
+}
+
+/// 事件所属的会话, 群事件为群号, 好友事件为好友uin, 同一会话的事件按顺序分发, 其他事件为0
+///
+/// bot_uin用于识别在其他设备上由机器人自己发出的好友消息和戳一戳
+pub(crate) fn conversation(event: &BotEvent, bot_uin: i64) -> i64 {
+    let event = match event {
+        BotEvent::QEvent(event) => event,
+        _ => return 0,
+    };
+    match event {
+        QEvent::GroupMessage(e) => e.inner.group_code,
+        QEvent::GroupTempMessage(e) => e.inner.group_code,
+        QEvent::GroupRequest(e) => e.inner.group_code,
+        QEvent::SelfInvited(e) => e.inner.group_code,
+        QEvent::NewMember(e) => e.inner.group_code,
+        QEvent::GroupMute(e) => e.inner.group_code,
+        QEvent::GroupMessageRecall(e) => e.inner.group_code,
+        QEvent::GroupLeave(e) => e.inner.group_code,
+        QEvent::GroupDisband(e) => e.inner.group_code,
+        QEvent::GroupPoke(e) => e.inner.group_code,
+        QEvent::GroupNameUpdate(e) => e.inner.group_code,
+        QEvent::MemberPermissionChange(e) => e.inner.group_code,
+        // 在其他设备上发送的好友消息from_uin为自己, 会话为接收的好友
+        QEvent::FriendMessage(e) if e.inner.from_uin == bot_uin => e.inner.target,
+        QEvent::FriendMessage(e) => e.inner.from_uin,
+        QEvent::FriendMessageRecall(e) => e.inner.friend_uin,
+        QEvent::FriendPoke(e) if e.inner.sender == bot_uin => e.inner.receiver,
+        QEvent::FriendPoke(e) => e.inner.sender,
+        QEvent::NewFriendRequest(e) => e.inner.req_uin,
+        QEvent::NewFriend(e) => e.inner.uin,
+        QEvent::DeleteFriend(e) => e.inner.uin,
+        _ => 0,
+    }
+}
+
+fn map_q_event(event: QEvent) -> Option<(&'static str, Vec<u8>)> {
+    match event {
+        QEvent::Login(uid) => encode("LoginEvent", obj::LoginEvent { uid }),
+        QEvent::GroupMessage(gm) => {
+            let inner = gm.inner;
+            encode(
+                "GroupMessageEvent",
+                obj::GroupMessageEvent {
+                    seqs: inner.seqs,
+                    rands: inner.rands,
+                    group_code: inner.group_code,
+                    group_name: inner.group_name,
+                    group_card: inner.group_card,
+                    from_uin: inner.from_uin,
+                    time: inner.time,
+                    elements: map_elements(inner.elements),
+                },
+            )
+        }
+        QEvent::FriendMessage(fm) => {
+            let inner = fm.inner;
+            encode(
+                "FriendMessageEvent",
+                obj::FriendMessageEvent {
+                    seqs: inner.seqs,
+                    rands: inner.rands,
+                    from_uin: inner.from_uin,
+                    time: inner.time,
+                    elements: map_elements(inner.elements),
+                    target: inner.target,
+                    from_nick: inner.from_nick,
+                },
+            )
+        }
+        QEvent::GroupAudioMessage(_) => None,
+        QEvent::FriendAudioMessage(_) => None,
+        QEvent::GroupTempMessage(gtm) => {
+            let inner = gtm.inner;
+            encode(
+                "GroupTempMessageEvent",
+                obj::GroupTempMessageEvent {
+                    seqs: inner.seqs,
+                    rands: inner.rands,
+                    group_code: inner.group_code,
+                    from_uin: inner.from_uin,
+                    from_nick: inner.from_nick,
+                    time: inner.time,
+                    elements: map_elements(inner.elements),
+                },
+            )
+        }
+        QEvent::GroupRequest(gr) => {
+            let inner = gr.inner;
+            encode(
+                "GroupRequestEvent",
+                obj::GroupRequestEvent {
+                    msg_seq: inner.msg_seq,
+                    msg_time: inner.msg_time,
+                    message: inner.message,
+                    req_uin: inner.req_uin,
+                    req_nick: inner.req_nick,
+                    group_code: inner.group_code,
+                    group_name: inner.group_name,
+                    suspicious: inner.suspicious,
+                    invitor_uin: inner.invitor_uin.unwrap_or_default(),
+                    invitor_nick: inner.invitor_nick.unwrap_or_default(),
+                },
+            )
+        }
+        QEvent::SelfInvited(si) => {
+            let inner = si.inner;
+            encode(
+                "SelfInvitedEvent",
+                obj::SelfInvitedEvent {
+                    msg_seq: inner.msg_seq,
+                    msg_time: inner.msg_time,
+                    invitor_uin: inner.invitor_uin,
+                    invitor_nick: inner.invitor_nick,
+                    group_code: inner.group_code,
+                    group_name: inner.group_name,
+                },
+            )
+        }
+        QEvent::NewFriendRequest(nfr) => {
+            let inner = nfr.inner;
+            encode(
+                "NewFriendRequestEvent",
+                obj::NewFriendRequestEvent {
+                    msg_seq: inner.msg_seq,
+                    message: inner.message,
+                    req_uin: inner.req_uin,
+                    req_nick: inner.req_nick,
+                },
+            )
+        }
+        QEvent::NewMember(nm) => encode(
+            "NewMemberEvent",
+            obj::NewMemberEvent {
+                group_code: nm.inner.group_code,
+                member_uin: nm.inner.member_uin,
+            },
+        ),
+        QEvent::GroupMute(gm) => encode(
+            "GroupMuteEvent",
+            obj::GroupMuteEvent {
+                group_code: gm.inner.group_code,
+                operator_uin: gm.inner.operator_uin,
+                target_uin: gm.inner.target_uin,
+                duration: gm.inner.duration.as_secs() as i64,
+            },
+        ),
+        QEvent::FriendMessageRecall(fmr) => encode(
+            "FriendMessageRecallEvent",
+            obj::FriendMessageRecallEvent {
+                msg_seq: fmr.inner.msg_seq,
+                friend_uin: fmr.inner.friend_uin,
+                time: fmr.inner.time,
+            },
+        ),
+        QEvent::GroupMessageRecall(gmr) => encode(
+            "GroupMessageRecallEvent",
+            obj::GroupMessageRecallEvent {
+                msg_seq: gmr.inner.msg_seq,
+                group_code: gmr.inner.group_code,
+                operator_uin: gmr.inner.operator_uin,
+                author_uin: gmr.inner.author_uin,
+                time: gmr.inner.time,
+            },
+        ),
+        QEvent::NewFriend(nf) => {
+            let inner = nf.inner;
+            encode(
+                "NewFriendEvent",
+                obj::NewFriendEvent {
+                    uin: inner.uin,
+                    nick: inner.nick,
+                    remark: inner.remark,
+                    face_id: inner.face_id as i32,
+                    group_id: inner.group_id as i32,
+                },
+            )
+        }
+        QEvent::GroupLeave(gl) => encode(
+            "GroupLeaveEvent",
+            obj::GroupLeaveEvent {
+                group_code: gl.inner.group_code,
+                member_uin: gl.inner.member_uin,
+                operator_uin: gl.inner.operator_uin.unwrap_or_default(),
+            },
+        ),
+        QEvent::GroupDisband(gd) => encode(
+            "GroupDisbandEvent",
+            obj::GroupDisbandEvent {
+                group_code: gd.inner.group_code,
+                operator_uin: gd.inner.operator_uin,
+            },
+        ),
+        QEvent::FriendPoke(fp) => encode(
+            "FriendPokeEvent",
+            obj::FriendPokeEvent {
+                sender: fp.inner.sender,
+                receiver: fp.inner.receiver,
+            },
+        ),
+        QEvent::GroupPoke(gp) => encode(
+            "GroupPokeEvent",
+            obj::GroupPokeEvent {
+                group_code: gp.inner.group_code,
+                sender: gp.inner.sender,
+                receiver: gp.inner.receiver,
+            },
+        ),
+        QEvent::GroupNameUpdate(gnu) => {
+            let inner = gnu.inner;
+            encode(
+                "GroupNameUpdateEvent",
+                obj::GroupNameUpdateEvent {
+                    group_code: inner.group_code,
+                    operator_uin: inner.operator_uin,
+                    group_name: inner.group_name,
+                },
+            )
+        }
+        QEvent::DeleteFriend(df) => encode(
+            "DeleteFriendEvent",
+            obj::DeleteFriendEvent { uin: df.inner.uin },
+        ),
+        QEvent::MemberPermissionChange(mpc) => encode(
+            "MemberPermissionChangeEvent",
+            obj::MemberPermissionChangeEvent {
+                group_code: mpc.inner.group_code,
+                member_uin: mpc.inner.member_uin,
+                new_permission: i32::from(map_permission(mpc.inner.new_permission)),
+            },
+        ),
+        QEvent::KickedOffline(ko) => {
+            let inner = ko.inner;
+            encode(
+                "KickedOfflineEvent",
+                obj::KickedOfflineEvent {
+                    uin: inner.uin,
+                    title: inner.title,
+                    tips: inner.tips,
+                    same_device: inner.same_device,
+                },
+            )
+        }
+        QEvent::MSFOffline(mo) => {
+            let inner = mo.inner;
+            encode(
+                "MSFOfflineEvent",
+                obj::MsfOfflineEvent {
+                    uin: inner.uin,
+                    title: inner.title,
+                    info: inner.info,
+                    kick_type: inner.kick_type as i32,
+                },
+            )
+        }
+        QEvent::ClientDisconnect(_) => {
+            encode("ClientDisconnectEvent", obj::ClientDisconnectEvent {})
+        }
+    }
+}
+
+pub(crate) fn map_permission(
+    permission: GroupMemberPermission,
+) -> obj::enums::GroupMemberPermission {
+    match permission {
+        GroupMemberPermission::Owner => obj::enums::GroupMemberPermission::Owner,
+        GroupMemberPermission::Administrator => obj::enums::GroupMemberPermission::Administrator,
+        GroupMemberPermission::Member => obj::enums::GroupMemberPermission::Member,
+    }
+}
+
+fn encode<E>(class_name: &'static str, event: E) -> Option<(&'static str, Vec<u8>)>
+where
+    E: Message,
+{
+    Some((class_name, event.encode_to_vec()))
+}
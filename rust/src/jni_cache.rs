@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Result};
+use jni::objects::{GlobalRef, JClass, JObject, JStaticMethodID};
+use jni::signature::ReturnType;
+use jni::sys::jvalue;
+use jni::JNIEnv;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::event::EVENT_CLASSES;
+
+const OBJ_PACKAGE: &str = "rijq/framework/obj";
+
+static CACHE: OnceLock<JniCache> = OnceLock::new();
+
+/// JNI_OnLoad时加载的类和静态方法, 附加到JVM的线程上找不到应用的类, 所以只能提前加载
+pub(crate) struct JniCache {
+    call_native_result: ProtoClass,
+    events: HashMap<&'static str, ProtoClass>,
+}
+
+/// protobuf生成的Java类和它的parseFrom方法
+pub(crate) struct ProtoClass {
+    class: GlobalRef,
+    parse_bytes: JStaticMethodID,
+    parse_buffer: JStaticMethodID,
+}
+
+/// 需要在加载动态库的线程上调用, 这时可以使用应用的类加载器
+pub(crate) fn init(env: &mut JNIEnv) -> Result<()> {
+    let call_native_result = ProtoClass::load(env, "CallNativeResult")?;
+    let mut events = HashMap::new();
+    for name in EVENT_CLASSES {
+        events.insert(*name, ProtoClass::load(env, name)?);
+    }
+    let _ = CACHE.set(JniCache {
+        call_native_result,
+        events,
+    });
+    Ok(())
+}
+
+pub(crate) fn get() -> Result<&'static JniCache> {
+    CACHE
+        .get()
+        .ok_or_else(|| anyhow!("JNI_OnLoad 未能加载Java类"))
+}
+
+impl JniCache {
+    pub(crate) fn call_native_result(&self) -> &ProtoClass {
+        &self.call_native_result
+    }
+
+    pub(crate) fn event(&self, name: &str) -> Result<&ProtoClass> {
+        self.events
+            .get(name)
+            .ok_or_else(|| anyhow!("未知的事件类 : {name}"))
+    }
+}
+
+impl ProtoClass {
+    fn load(env: &mut JNIEnv, name: &str) -> Result<Self> {
+        let class = env.find_class(format!("{OBJ_PACKAGE}/{name}"))?;
+        let parse_bytes =
+            env.get_static_method_id(&class, "parseFrom", format!("([B)L{OBJ_PACKAGE}/{name};"))?;
+        let parse_buffer = env.get_static_method_id(
+            &class,
+            "parseFrom",
+            format!("(Ljava/nio/ByteBuffer;)L{OBJ_PACKAGE}/{name};"),
+        )?;
+        Ok(Self {
+            class: env.new_global_ref(class)?,
+            parse_bytes,
+            parse_buffer,
+        })
+    }
+
+    /// 复制到Java的byte[]后解析
+    pub(crate) fn parse_bytes<'local>(
+        &self,
+        env: &mut JNIEnv<'local>,
+        data: &[u8],
+    ) -> jni::errors::Result<JObject<'local>> {
+        let bytes = env.byte_array_from_slice(data)?;
+        self.parse(env, self.parse_bytes, &bytes)
+    }
+
+    /// 通过direct ByteBuffer直接读取data, protobuf解析时会复制需要的字段, 返回后data可以释放
+    pub(crate) fn parse_direct<'local>(
+        &self,
+        env: &mut JNIEnv<'local>,
+        data: &mut [u8],
+    ) -> jni::errors::Result<JObject<'local>> {
+        let buffer = unsafe { env.new_direct_byte_buffer(data.as_mut_ptr(), data.len())? };
+        self.parse(env, self.parse_buffer, &buffer)
+    }
+
+    fn parse<'local>(
+        &self,
+        env: &mut JNIEnv<'local>,
+        method: JStaticMethodID,
+        arg: &JObject,
+    ) -> jni::errors::Result<JObject<'local>> {
+        unsafe {
+            env.call_static_method_unchecked(
+                <&JClass>::from(self.class.as_obj()),
+                method,
+                ReturnType::Object,
+                &[jvalue { l: arg.as_raw() }],
+            )?
+            .l()
+        }
+    }
+}
@@ -1,21 +1,26 @@
-use jni::objects::{JByteArray, JClass, JObject, JString};
-use jni::signature::{Primitive, ReturnType};
-use jni::sys::{jlong, jvalue};
-use jni::JNIEnv;
+use anyhow::Context;
+use jni::objects::{GlobalRef, JByteArray, JClass, JObject, JString};
+use jni::sys::{jint, jlong, jvalue, JNI_VERSION_1_8};
+use jni::{JNIEnv, JavaVM};
 use prost::Message;
 use ricq::handler::QEvent;
-use ricq::version::ANDROID_WATCH;
-use ricq_core::msg::elem::{FlashImage, FriendImage, GroupImage, RQElem};
+use ricq_core::msg::elem::{self, FlashImage, FriendImage, GroupImage, RQElem};
 use ricq_core::msg::MessageChain;
-use ricq_core::protocol::device::Device;
+use ricq_core::structs::{FriendInfo, GroupInfo, GroupMemberInfo, MessageReceipt};
 use std::default::Default;
+use std::ffi::c_void;
 use std::io::Cursor;
-use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::runtime::Runtime;
 
-use crate::run::run_ricq;
+use crate::error::NativeError;
+use crate::event::BotEvent;
+use crate::lifecycle::Lifecycle;
+use crate::obj::enums::ConnectionState;
+use crate::queue::EventQueue;
+use crate::run::LoginInteraction;
+use crate::session::{Bot, Session};
+use crate::status::Status;
 
 mod obj {
     pub(crate) use super::enums;
@@ -25,370 +30,320 @@ mod obj {
 mod enums {
     include!(concat!(env!("OUT_DIR"), "/enums.rs"));
 }
+#[cfg(feature = "bench")]
+mod bench;
+mod captcha;
+mod config;
+mod crypto;
+mod device;
+mod dispatch;
+mod error;
+mod event;
+mod jni_cache;
+mod lifecycle;
 mod log;
+mod native;
+mod queue;
+mod reconnect;
 mod run;
+mod session;
+mod status;
+mod store;
+mod token;
+
+/// 加载动态库时缓存Java类和方法, 失败时调用daemon和callNative会返回错误
+#[no_mangle]
+pub extern "system" fn JNI_OnLoad(vm: JavaVM, _reserved: *mut c_void) -> jint {
+    if let Ok(mut env) = vm.get_env() {
+        if let Err(err) = jni_cache::init(&mut env) {
+            // 这时日志还没有初始化
+            eprintln!("rijq JNI_OnLoad error : {:?}", err);
+            let _ = env.exception_describe();
+            let _ = env.exception_clear();
+        }
+    }
+    JNI_VERSION_1_8
+}
+
+/// daemon结束时等待异步调用和运行时中任务的时间
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
 
 struct JHandler {
-    sender: Arc<tokio::sync::mpsc::UnboundedSender<QEvent>>,
+    events: Arc<EventQueue>,
+    status: Arc<Status>,
 }
 
 #[async_trait::async_trait]
 impl ricq::handler::Handler for JHandler {
     async fn handle(&self, event: QEvent) {
-        self.sender.send(event).unwrap();
+        // 收到服务器推送的事件说明连接可用
+        self.status.record_activity();
+        if let QEvent::Login(uin) = event {
+            self.status.set_uin(uin);
+        }
+        self.events
+            .push(BotEvent::QEvent(event), self.status.uin())
+            .await;
     }
 }
 
 #[no_mangle]
-#[allow(unused_mut)]
 pub extern "system" fn Java_rijq_framework_handlers_InitRunner_daemon(
     mut env: JNIEnv,
     runner: JObject,
+    config: JByteArray,
+    session_store: JObject,
 ) {
-    log::init_log_once();
+    error::catch_jni(&mut env, (), |env| {
+        daemon(env, &runner, config, &session_store)
+    })
+}
+
+fn daemon(
+    env: &mut JNIEnv,
+    runner: &JObject,
+    config: JByteArray,
+    session_store: &JObject,
+) -> anyhow::Result<()> {
+    // 解析配置, 在连接服务器之前校验
+    let config = config::decode(env.convert_byte_array(config)?.as_slice())?;
+    log::init_log_once(config::log_level(&config)?);
     // 提示daemon启动
     tracing::info!("daemon start");
+    let device_config = config.device.clone().unwrap_or_default();
+    let protocol = device::protocol(&device_config)?;
+    let store_config = config.store.clone().unwrap_or_default();
+    let cipher = crypto::StoreCipher::from_config(&store_config)?.map(Arc::new);
+    let session_store = store::session_store(env, &store_config, session_store, cipher.clone())?;
     // 启动runtime
-    let runtime = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .thread_keep_alive(Duration::from_secs(100))
-        .worker_threads(10)
-        .max_blocking_threads(10)
-        .build()
-        .unwrap();
+    let runtime = config::build_runtime(&config)?;
     tracing::info!("runtime init");
-    // 初始化channel，启动ricq
-    let (sender, mut r) = tokio::sync::mpsc::unbounded_channel::<QEvent>();
-    let sender = Arc::new(sender);
-    let device = runtime.block_on(device());
+    // 初始化事件队列，启动ricq
+    let dispatch_config = config::dispatch_config(&config);
+    let events = Arc::new(EventQueue::new(
+        &config::event_queue_config(&config),
+        dispatch_config.threads as usize,
+    ));
+    let status = Arc::new(Status::default());
+    let device = runtime.block_on(device::device(&device_config, cipher.as_deref()))?;
     let client = ricq::Client::new(
         device,
-        ANDROID_WATCH,
+        protocol,
         JHandler {
-            sender: sender.clone(),
+            events: events.clone(),
+            status: status.clone(),
         },
     );
-    let client = Arc::new(client);
-    let c1 = client.clone();
-    let _ = runtime.spawn(async move { run_ricq(c1, sender).await.unwrap() });
-    // 获取env,runtime,client的指针, 并传递给InitRunner
-    let env_point = &env as *const JNIEnv as i64;
-    println!("got env_point : {env_point}");
-    let runtime_point = &runtime as *const Runtime as i64;
-    println!("got runtime_point : {runtime_point}");
-    let client_point = &client as *const Arc<ricq::Client> as i64;
-    println!("got client_point : {client_point}");
-    env.call_method(
-        &runner,
-        "setEnvPoints",
-        "(JJJ)V",
-        &[
-            jni::objects::JValue::Long(env_point),
-            jni::objects::JValue::Long(runtime_point),
-            jni::objects::JValue::Long(client_point),
-        ],
-    )
-    .unwrap();
-    println!("EnvPoints set");
-    // 获取runner的dispatchEventMethodPoint方法
-    let runner_class = env.get_object_class(&runner).unwrap();
-    tracing::info!("got runner class");
-    let dispatch_method = env
-        .get_method_id(
-            &runner_class,
-            "dispatchEventMethodPoint",
-            "(Ljava/lang/Object;)V",
-        )
-        .unwrap();
-    // 获取LoginEvent类
-    let login_event_class = env.find_class("rijq/framework/obj/LoginEvent").unwrap();
-    let group_message_class = env
-        .find_class("rijq/framework/obj/GroupMessageEvent")
-        .unwrap();
-    let friend_message_class = env
-        .find_class("rijq/framework/obj/FriendMessageEvent")
-        .unwrap();
-    // 开始接收事件
-    while let Some(event) = runtime.block_on(r.recv()) {
-        println!("event : {:?}", event);
-        match event {
-            QEvent::Login(uid) => {
-                let event = obj::LoginEvent { uid };
-                let data = event.encode_to_vec();
-                let data = env.byte_array_from_slice(data.as_slice()).unwrap();
-                let de = env
-                    .call_static_method(
-                        &login_event_class,
-                        "parseFrom",
-                        "([B)Lrijq/framework/obj/LoginEvent;",
-                        &[(&data).into()],
-                    )
-                    .unwrap();
-                unsafe {
-                    env.call_method_unchecked(
-                        &runner,
-                        &dispatch_method,
-                        ReturnType::Primitive(Primitive::Void),
-                        &[de.as_jni()],
-                    )
-                    .unwrap();
-                }
-            }
-            QEvent::GroupMessage(gm) => {
-                let inner = gm.inner;
-                let event = obj::GroupMessageEvent {
-                    seqs: inner.seqs,
-                    rands: inner.rands,
-                    group_code: inner.group_code,
-                    group_name: inner.group_name,
-                    group_card: inner.group_card,
-                    from_uin: inner.from_uin,
-                    time: inner.time,
-                    elements: map_elements(inner.elements),
-                };
-                let data = event.encode_to_vec();
-                let data = env.byte_array_from_slice(data.as_slice()).unwrap();
-                let de = env
-                    .call_static_method(
-                        &group_message_class,
-                        "parseFrom",
-                        "([B)Lrijq/framework/obj/GroupMessageEvent;",
-                        &[(&data).into()],
-                    )
-                    .unwrap();
-                unsafe {
-                    env.call_method_unchecked(
-                        &runner,
-                        &dispatch_method,
-                        ReturnType::Primitive(Primitive::Void),
-                        &[de.as_jni()],
-                    )
-                    .unwrap();
-                }
-            }
-            QEvent::FriendMessage(fm) => {
-                let inner = fm.inner;
-                let event = obj::FriendMessageEvent {
-                    seqs: inner.seqs,
-                    rands: inner.rands,
-                    from_uin: inner.from_uin,
-                    time: inner.time,
-                    elements: map_elements(inner.elements),
-                    target: inner.target,
-                    from_nick: inner.from_nick,
-                };
-                let data = event.encode_to_vec();
-                let data = env.byte_array_from_slice(data.as_slice()).unwrap();
-                let de = env
-                    .call_static_method(
-                        &friend_message_class,
-                        "parseFrom",
-                        "([B)Lrijq/framework/obj/FriendMessageEvent;",
-                        &[(&data).into()],
-                    )
-                    .unwrap();
-                unsafe {
-                    env.call_method_unchecked(
-                        &runner,
-                        &dispatch_method,
-                        ReturnType::Primitive(Primitive::Void),
-                        &[de.as_jni()],
-                    )
-                    .unwrap();
-                }
-            }
-            QEvent::GroupAudioMessage(_) => {}
-            QEvent::FriendAudioMessage(_) => {}
-            QEvent::GroupTempMessage(_) => {}
-            QEvent::GroupRequest(_) => {}
-            QEvent::SelfInvited(_) => {}
-            QEvent::NewFriendRequest(_) => {}
-            QEvent::NewMember(_) => {}
-            QEvent::GroupMute(_) => {}
-            QEvent::FriendMessageRecall(_) => {}
-            QEvent::GroupMessageRecall(_) => {}
-            QEvent::NewFriend(_) => {}
-            QEvent::GroupLeave(_) => {}
-            QEvent::GroupDisband(_) => {}
-            QEvent::FriendPoke(_) => {}
-            QEvent::GroupPoke(_) => {}
-            QEvent::GroupNameUpdate(_) => {}
-            QEvent::DeleteFriend(_) => {}
-            QEvent::MemberPermissionChange(_) => {}
-            QEvent::KickedOffline(_) => {}
-            QEvent::MSFOffline(_) => {}
-            QEvent::ClientDisconnect(_) => {}
+    let bot = Arc::new(Bot {
+        client: Arc::new(client),
+        login: LoginInteraction::default(),
+        events,
+        session_store,
+        lifecycle: Lifecycle::new(config),
+        status,
+    });
+    {
+        let _guard = runtime.enter();
+        bot.lifecycle.start(bot.clone());
+    }
+    // 注册会话
+    let session = Arc::new(Session {
+        runtime,
+        bot,
+        calls: Default::default(),
+    });
+    let handle = session::register(session.clone());
+    tracing::info!("session registered : {handle}");
+    let result = dispatch::dispatch_events(env, runner, handle, &session, &dispatch_config);
+    // 事件循环出错结束时机器人仍在运行, 停止客户端并保存会话
+    if session.bot.status.state() != ConnectionState::Stopped {
+        let bot = &session.bot;
+        if let Err(err) = session.runtime.block_on(bot.lifecycle.shutdown(bot)) {
+            tracing::warn!("停止机器人失败 : {:?}", err);
         }
     }
-    let _ = client;
-    // let user2 = unsafe { &mut *(env_point as *mut jni::JNIEnv) };
-    // user2.find_class("/").unwrap();
+    session::close(handle);
+    tracing::info!("session closed : {handle}");
+    // 运行时关闭时会丢弃未完成的任务, 先等待正在进行的异步调用
+    let calls = session.calls.clone();
+    if session
+        .runtime
+        .block_on(tokio::time::timeout(STOP_TIMEOUT, calls.wait_idle()))
+        .is_err()
+    {
+        tracing::warn!("异步调用未能在停止前完成");
+    }
+    // 没有正在进行的同步调用时, 等待运行时中的任务结束
+    if let Ok(session) = Arc::try_unwrap(session) {
+        session.runtime.shutdown_timeout(STOP_TIMEOUT);
+    }
+    // 仍未完成的future以错误结束, 避免Java侧一直等待
+    for future in calls.close() {
+        fail_stopped(env, &future);
+    }
+    result
 }
 
-async fn device() -> Device {
-    let file_name = "device.json";
-    if Path::new(file_name).exists() {
-        serde_json::from_str(&tokio::fs::read_to_string(file_name).await.unwrap()).unwrap()
-    } else {
-        let device = Device::random();
-        tokio::fs::write(file_name, serde_json::to_string(&device).unwrap())
-            .await
-            .unwrap();
-        device
+fn fail_stopped(env: &mut JNIEnv, future: &GlobalRef) {
+    let result = native::fail_result(NativeError::new(
+        obj::enums::ErrorType::IllegalState,
+        "bot stopped before the call completed",
+    ));
+    if let Err(err) = complete_future(env, future, result) {
+        tracing::error!("complete future error : {:?}", err);
+        let _ = env.exception_describe();
+        let _ = env.exception_clear();
     }
 }
 
 #[no_mangle]
-#[allow(unused_mut)]
-pub extern "system" fn Java_rijq_framework_handlers_InitRunner_callNative(
-    mut _env: JNIEnv,
+pub extern "system" fn Java_rijq_framework_handlers_InitRunner_nativeCall(
+    mut env: JNIEnv,
     _class: JClass,
-    _env_point: jlong,
-    _runtime_point: jlong,
-    _client_point: jlong,
+    _handle: jlong,
     _message_type: JString,
     _message: JByteArray,
 ) -> jvalue {
-    tracing::debug!("callNative : {_env_point}, {_runtime_point}, {_client_point}");
-    let runtime = unsafe { &*(_runtime_point as *const Runtime) };
-    let client = unsafe { &*(_client_point as *const Arc<ricq::Client>) };
+    error::catch_jni(
+        &mut env,
+        jvalue {
+            l: std::ptr::null_mut(),
+        },
+        |env| call_native(env, _handle, _message_type, _message),
+    )
+}
+
+fn call_native(
+    env: &mut JNIEnv,
+    _handle: jlong,
+    _message_type: JString,
+    _message: JByteArray,
+) -> anyhow::Result<jvalue> {
     // _message_type 转换成 str
-    let message_type: String = _env
+    let message_type: String = env
         .get_string(&_message_type)
-        .expect("Couldn't get java string!")
+        .context("Couldn't get java string!")?
         .into();
     // _message 转换成 Vec<u8>
-    let message: Vec<u8> = _env
+    let message: Vec<u8> = env
         .convert_byte_array(_message)
-        .expect("Couldn't get java byte array!")
-        .into();
-    // log
-    tracing::debug!("callNative : {message_type}");
+        .context("Couldn't get java byte array!")?;
     // process
-    match message_type.as_str() {
-        "SendFriendMessage" => {
-            let message: obj::SendFriendMessage =
-                match obj::SendFriendMessage::decode(&mut Cursor::new(message)) {
-                    Ok(message) => message,
-                    Err(err) => {
-                        return fail_result(
-                            _env,
-                            vec!["parse SendFriendMessage error", err.to_string().as_str()],
-                        );
-                    }
-                };
-            let target = message.target;
-            let message = map_send(message.elements);
-            return match runtime
-                .block_on(async move { client.send_friend_message(target, message).await })
-            {
-                Ok(_) => encode_result(
-                    _env,
-                    obj::CallNativeResult {
-                        code: obj::enums::ResultType::Success as i32,
-                        ..Default::default()
-                    },
-                ),
-                Err(err) => fail_result(
-                    _env,
-                    vec!["SendFriendMessage error", err.to_string().as_str()],
-                ),
-            };
+    let result = match session::get(_handle) {
+        Ok(session) => {
+            session
+                .runtime
+                .block_on(native::call(session.bot.clone(), message_type, message))
         }
-        "UploadImage" => {
-            let message: obj::UploadImageDto =
-                match obj::UploadImageDto::decode(&mut Cursor::new(message)) {
-                    Ok(message) => message,
-                    Err(err) => {
-                        return fail_result(
-                            _env,
-                            vec!["parse UploadImageDto error", err.to_string().as_str()],
-                        );
-                    }
-                };
-            return if message.target_type == obj::enums::SendTargetType::Friend as i32 {
-                match runtime.block_on(async move {
-                    client
-                        .upload_friend_image(message.target, message.data.as_slice())
-                        .await
-                }) {
-                    Ok(img) => success_result(_env, map_friend_image(img, false)),
-                    Err(err) => {
-                        fail_result(_env, vec!["UploadImage error", err.to_string().as_str()])
-                    }
-                }
-            } else if message.target_type == obj::enums::SendTargetType::Group as i32 {
-                match runtime.block_on(async move {
-                    client
-                        .upload_group_image(message.target, message.data.as_slice())
-                        .await
-                }) {
-                    Ok(img) => success_result(_env, map_group_image(img, false)),
-                    Err(err) => {
-                        fail_result(_env, vec!["UploadImage error", err.to_string().as_str()])
-                    }
-                }
-            } else {
-                fail_result(_env, vec!["unknown target type"])
-            };
-        }
-        _ => fail_result(_env, vec!["unknown message type"]),
-    }
+        Err(err) => native::fail_result(err),
+    };
+    encode_result(env, result)
 }
 
-fn success_result<S>(env: JNIEnv, messages: S) -> jvalue
-where
-    S: prost::Message,
-{
-    encode_result(
-        env,
-        obj::CallNativeResult {
-            code: obj::enums::ResultType::Fail as i32,
-            data: messages.encode_to_vec(),
-            ..Default::default()
-        },
-    )
+#[no_mangle]
+pub extern "system" fn Java_rijq_framework_handlers_InitRunner_nativeCallAsync(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    message_type: JString,
+    message: JByteArray,
+    future: JObject,
+) {
+    error::catch_jni(&mut env, (), |env| {
+        call_native_async(env, handle, message_type, message, future)
+    })
 }
 
-fn fail_result<S>(env: JNIEnv, messages: Vec<S>) -> jvalue
-where
-    S: AsRef<str>,
-{
-    encode_result(
-        env,
-        obj::CallNativeResult {
-            code: obj::enums::ResultType::Fail as i32,
-            message: {
-                let mut message = String::new();
-                for m in messages {
-                    message.push_str(m.as_ref());
-                    message.push_str(". ");
-                }
-                message
-            },
-            ..Default::default()
-        },
-    )
+/// 在tokio中执行callNative, 完成后在附加到JVM的worker线程上complete传入的CompletableFuture
+///
+/// Java侧的后续处理使用thenApplyAsync切换到其他线程, 不会在worker线程上调用同步的nativeCall
+fn call_native_async(
+    env: &mut JNIEnv,
+    handle: jlong,
+    message_type: JString,
+    message: JByteArray,
+    future: JObject,
+) -> anyhow::Result<()> {
+    let message_type: String = env
+        .get_string(&message_type)
+        .context("Couldn't get java string!")?
+        .into();
+    let message: Vec<u8> = env
+        .convert_byte_array(message)
+        .context("Couldn't get java byte array!")?;
+    let future = env.new_global_ref(future)?;
+    let session = match session::get(handle) {
+        Ok(session) => session,
+        Err(err) => {
+            return complete_future(env, &future, native::fail_result(err));
+        }
+    };
+    let id = match session.calls.insert(future) {
+        Ok(id) => id,
+        Err(future) => {
+            fail_stopped(env, &future);
+            return Ok(());
+        }
+    };
+    let vm = env.get_java_vm()?;
+    let bot = session.bot.clone();
+    let calls = session.calls.clone();
+    session.runtime.spawn(async move {
+        // 单独spawn一次, 即使panic也能complete future
+        let result = match tokio::spawn(native::call(bot, message_type, message)).await {
+            Ok(result) => result,
+            Err(err) => native::fail_result(NativeError::new(
+                obj::enums::ErrorType::Internal,
+                if err.is_panic() {
+                    error::panic_message(err.into_panic().as_ref())
+                } else {
+                    err.to_string()
+                },
+            )),
+        };
+        // daemon结束时已经被取走并以错误结束
+        let future = match calls.take(id) {
+            Some(future) => future,
+            None => return,
+        };
+        let mut env = match vm.attach_current_thread_as_daemon() {
+            Ok(env) => env,
+            Err(err) => {
+                tracing::error!("attach thread error : {:?}", err);
+                return;
+            }
+        };
+        if let Err(err) = complete_future(&mut env, &future, result) {
+            tracing::error!("complete future error : {:?}", err);
+            let _ = env.exception_describe();
+            let _ = env.exception_clear();
+        }
+    });
+    Ok(())
+}
+
+fn complete_future(
+    env: &mut JNIEnv,
+    future: &GlobalRef,
+    result: obj::CallNativeResult,
+) -> anyhow::Result<()> {
+    let result_class = jni_cache::get()?.call_native_result();
+    env.with_local_frame(4, |env| -> jni::errors::Result<()> {
+        let de = result_class.parse_bytes(env, result.encode_to_vec().as_slice())?;
+        env.call_method(future, "complete", "(Ljava/lang/Object;)Z", &[(&de).into()])?;
+        Ok(())
+    })?;
+    Ok(())
 }
 
-fn encode_result(mut env: JNIEnv, result: obj::CallNativeResult) -> jvalue {
-    let data = result.encode_to_vec();
-    let call_native_class = env
-        .find_class("rijq/framework/obj/CallNativeResult")
-        .unwrap();
-    let data = env.byte_array_from_slice(data.as_slice()).unwrap();
-    let de = env
-        .call_static_method(
-            &call_native_class,
-            "parseFrom",
-            "([B)Lrijq/framework/obj/CallNativeResult;",
-            &[(&data).into()],
-        )
-        .unwrap();
-    return de.as_jni();
+fn encode_result(env: &mut JNIEnv, result: obj::CallNativeResult) -> anyhow::Result<jvalue> {
+    let de = jni_cache::get()?
+        .call_native_result()
+        .parse_bytes(env, result.encode_to_vec().as_slice())?;
+    Ok(jvalue { l: de.into_raw() })
 }
 
-fn map_elements(chain: MessageChain) -> Vec<obj::MessageElement> {
+pub(crate) fn map_elements(chain: MessageChain) -> Vec<obj::MessageElement> {
     let mut vc = vec![];
     for element in chain {
         match element {
@@ -494,15 +449,132 @@ fn map_elements(chain: MessageChain) -> Vec<obj::MessageElement> {
     vc
 }
 
-fn map_send(elements: Vec<obj::MessageElement>) -> MessageChain {
+fn map_send(elements: Vec<obj::MessageElement>) -> anyhow::Result<MessageChain> {
     let mut chain = MessageChain::default();
     for x in elements {
-        if x.element_type == i32::from(obj::enums::ElementType::Text) {
-            let text = obj::Text::decode(&mut Cursor::new(x.element_data)).expect("Text decode");
-            chain.push(ricq_core::msg::elem::Text::new(text.content));
+        match obj::enums::ElementType::from_i32(x.element_type) {
+            Some(obj::enums::ElementType::Text) => {
+                let text: obj::Text = decode_element(x.element_data, "Text")?;
+                chain.push(elem::Text::new(text.content));
+            }
+            Some(obj::enums::ElementType::At) => {
+                let at: obj::At = decode_element(x.element_data, "At")?;
+                chain.push(elem::At {
+                    target: at.target,
+                    display: at.display,
+                });
+            }
+            Some(obj::enums::ElementType::Face) => {
+                let face: obj::Face = decode_element(x.element_data, "Face")?;
+                chain.push(elem::Face {
+                    index: face.index,
+                    name: face.name,
+                });
+            }
+            Some(obj::enums::ElementType::MarketFace) => {
+                let market_face: obj::MarketFace = decode_element(x.element_data, "MarketFace")?;
+                chain.push(elem::MarketFace {
+                    name: market_face.name,
+                    face_id: market_face.face_id,
+                    tab_id: market_face.tab_id,
+                    item_type: market_face.item_type,
+                    sub_type: market_face.sub_type,
+                    media_type: market_face.media_type,
+                    encrypt_key: market_face.encrypt_key,
+                    magic_value: market_face.magic_value,
+                });
+            }
+            Some(obj::enums::ElementType::Dice) => {
+                let dice: obj::Dice = decode_element(x.element_data, "Dice")?;
+                chain.push(elem::Dice { value: dice.value });
+            }
+            Some(obj::enums::ElementType::FriendImage) => {
+                let friend_image: obj::FriendImage = decode_element(x.element_data, "FriendImage")?;
+                let flash = friend_image.flash;
+                let friend_image = map_send_friend_image(friend_image);
+                if flash {
+                    chain.push(FlashImage::FriendImage(friend_image));
+                } else {
+                    chain.push(friend_image);
+                }
+            }
+            Some(obj::enums::ElementType::GroupImage) => {
+                let group_image: obj::GroupImage = decode_element(x.element_data, "GroupImage")?;
+                let flash = group_image.flash;
+                let group_image = map_send_group_image(group_image);
+                if flash {
+                    chain.push(FlashImage::GroupImage(group_image));
+                } else {
+                    chain.push(group_image);
+                }
+            }
+            Some(element_type) => {
+                return Err(anyhow::anyhow!(
+                    "unsupported element type : {:?}",
+                    element_type
+                ));
+            }
+            None => {
+                return Err(anyhow::anyhow!("unknown element type : {}", x.element_type));
+            }
         }
     }
-    chain
+    Ok(chain)
+}
+
+fn decode_element<T>(data: Vec<u8>, name: &str) -> anyhow::Result<T>
+where
+    T: prost::Message + Default,
+{
+    T::decode(&mut Cursor::new(data)).map_err(|err| anyhow::anyhow!("{} decode : {}", name, err))
+}
+
+fn map_receipt(receipt: MessageReceipt) -> obj::MessageReceipt {
+    obj::MessageReceipt {
+        seqs: receipt.seqs,
+        rands: receipt.rands,
+        time: receipt.time,
+    }
+}
+
+fn map_friend_info(friend: FriendInfo) -> obj::FriendInfo {
+    obj::FriendInfo {
+        uin: friend.uin,
+        nick: friend.nick,
+        remark: friend.remark,
+        face_id: friend.face_id as i32,
+        group_id: friend.group_id as i32,
+    }
+}
+
+fn map_group_info(group: GroupInfo) -> obj::GroupInfo {
+    obj::GroupInfo {
+        code: group.code,
+        uin: group.uin,
+        name: group.name,
+        memo: group.memo,
+        owner_uin: group.owner_uin,
+        create_time: group.group_create_time,
+        member_count: group.member_count as u32,
+        max_member_count: group.max_member_count as u32,
+        shut_up_timestamp: group.shut_up_timestamp,
+        my_shut_up_timestamp: group.my_shut_up_timestamp,
+    }
+}
+
+fn map_group_member_info(member: GroupMemberInfo) -> obj::GroupMemberInfo {
+    obj::GroupMemberInfo {
+        group_code: member.group_code,
+        uin: member.uin,
+        nickname: member.nickname,
+        card_name: member.card_name,
+        permission: i32::from(event::map_permission(member.permission)),
+        join_time: member.join_time,
+        last_speak_time: member.last_speak_time,
+        shut_up_timestamp: member.shut_up_timestamp,
+        special_title: member.special_title,
+        level: member.level as i32,
+    }
 }
 
 fn map_friend_image(friend_image: FriendImage, flash: bool) -> obj::FriendImage {
@@ -520,6 +592,20 @@ fn map_friend_image(friend_image: FriendImage, flash: bool) -> obj::FriendImage
     }
 }
 
+fn map_send_friend_image(friend_image: obj::FriendImage) -> FriendImage {
+    FriendImage {
+        res_id: friend_image.res_id,
+        file_path: friend_image.file_path,
+        md5: friend_image.md5,
+        size: friend_image.size,
+        width: friend_image.width,
+        height: friend_image.height,
+        image_type: friend_image.image_type,
+        orig_url: friend_image.orig_url,
+        download_path: friend_image.download_path,
+    }
+}
+
 fn map_group_image(group_image: GroupImage, flash: bool) -> obj::GroupImage {
     obj::GroupImage {
         file_path: group_image.file_path,
@@ -536,3 +622,23 @@ fn map_group_image(group_image: GroupImage, flash: bool) -> obj::GroupImage {
         flash,
     }
 }
+
+fn map_send_group_image(group_image: obj::GroupImage) -> GroupImage {
+    GroupImage {
+        file_path: group_image.file_path,
+        file_id: group_image.file_id,
+        size: group_image.size,
+        width: group_image.width,
+        height: group_image.height,
+        md5: group_image.md5,
+        orig_url: if group_image.orig_url.is_empty() {
+            None
+        } else {
+            Some(group_image.orig_url)
+        },
+        image_type: group_image.image_type,
+        signature: group_image.signature,
+        server_ip: group_image.server_ip,
+        server_port: group_image.server_port,
+    }
+}
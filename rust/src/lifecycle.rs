@@ -0,0 +1,78 @@
+use anyhow::Result;
+use ricq::client::NetworkStatus;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::event::BotEvent;
+use crate::obj;
+use crate::obj::enums::ConnectionState;
+use crate::run::{run_ricq, write_token_to_store};
+use crate::session::Bot;
+
+/// 机器人的启动、停止和重启, 停止或者连接和登录失败后事件循环结束, daemon返回
+pub(crate) struct Lifecycle {
+    config: obj::RijqConfig,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Lifecycle {
+    pub(crate) fn new(config: obj::RijqConfig) -> Self {
+        Self {
+            config,
+            task: Mutex::new(None),
+        }
+    }
+
+    /// 在tokio运行时中启动连接和登录, 需要在运行时上下文中调用
+    pub(crate) fn start(&self, bot: Arc<Bot>) {
+        let config = self.config.clone();
+        let task = tokio::spawn(async move {
+            if let Err(err) = run_ricq(bot.clone(), config).await {
+                tracing::error!("ricq stopped : {:?}", err);
+                bot.client.stop(NetworkStatus::Stop);
+                bot.status.set_state(ConnectionState::Stopped);
+                // 不会再重连, 通知Java后结束事件循环, daemon返回
+                bot.emit(BotEvent::Disconnected(obj::DisconnectedEvent {
+                    reason: format!("{err:#}"),
+                    stopped: true,
+                }))
+                .await;
+                bot.events.close();
+            }
+        });
+        if let Some(old) = self.task.lock().unwrap().replace(task) {
+            old.abort();
+        }
+    }
+
+    /// 停止连接和重连, 已登录时把最新的token写入会话存储
+    async fn stop_client(&self, bot: &Bot) -> Result<()> {
+        if let Some(task) = self.task.lock().unwrap().take() {
+            task.abort();
+        }
+        let flush = if bot.status.state() == ConnectionState::Online {
+            write_token_to_store(bot, bot.client.gen_token().await).await
+        } else {
+            Ok(())
+        };
+        bot.client.stop(NetworkStatus::Stop);
+        bot.login.cancel();
+        bot.status.set_state(ConnectionState::Stopped);
+        flush
+    }
+
+    pub(crate) async fn shutdown(&self, bot: &Bot) -> Result<()> {
+        tracing::info!("停止机器人");
+        let result = self.stop_client(bot).await;
+        // 即使保存会话失败也要结束事件循环
+        bot.events.close();
+        result
+    }
+
+    pub(crate) async fn restart(&self, bot: &Arc<Bot>) -> Result<()> {
+        tracing::info!("重启机器人");
+        let result = self.stop_client(bot).await;
+        self.start(bot.clone());
+        result
+    }
+}
@@ -1,20 +1,15 @@
-use lazy_static::lazy_static;
-use std::sync::Mutex;
+use std::sync::Once;
+use tracing::metadata::LevelFilter;
 
-lazy_static! {
-    static ref INIT: Mutex<()> = {
-        init_tracing_subscriber();
-        Mutex::new(())
-    };
-}
+static INIT: Once = Once::new();
 
-fn init_tracing_subscriber() {
+fn init_tracing_subscriber(level: LevelFilter) {
     println!("logger init");
-    tracing_subscriber::fmt::init();
+    tracing_subscriber::fmt().with_max_level(level).init();
     tracing::info!("logger hello");
 }
 
-pub(crate) fn init_log_once() {
-    let lock = INIT.lock().unwrap();
-    drop(lock);
+/// 日志只初始化一次, 之后的daemon使用第一次的日志级别
+pub(crate) fn init_log_once(level: LevelFilter) {
+    INIT.call_once(|| init_tracing_subscriber(level));
 }
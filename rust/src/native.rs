@@ -0,0 +1,374 @@
+use prost::Message;
+use ricq_core::structs::GroupMemberPermission;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{NativeError, NativeResultExt};
+use crate::obj;
+use crate::obj::enums::ErrorType;
+use crate::run::DeviceLockAction;
+use crate::session::Bot;
+use crate::{
+    map_friend_image, map_friend_info, map_group_image, map_group_info, map_group_member_info,
+    map_receipt, map_send,
+};
+
+/// 执行一次callNative, 同步和异步调用共用
+pub(crate) async fn call(
+    bot: Arc<Bot>,
+    message_type: String,
+    message: Vec<u8>,
+) -> obj::CallNativeResult {
+    tracing::debug!("callNative : {message_type}");
+    match dispatch(&bot, message_type.as_str(), message).await {
+        Ok(data) => obj::CallNativeResult {
+            code: obj::enums::ResultType::Success as i32,
+            data,
+            ..Default::default()
+        },
+        Err(err) => fail_result(err),
+    }
+}
+
+pub(crate) fn fail_result(err: NativeError) -> obj::CallNativeResult {
+    obj::CallNativeResult {
+        code: obj::enums::ResultType::Fail as i32,
+        error_type: i32::from(err.error_type),
+        message: err.message,
+        ..Default::default()
+    }
+}
+
+async fn dispatch(
+    bot: &Arc<Bot>,
+    message_type: &str,
+    message: Vec<u8>,
+) -> Result<Vec<u8>, NativeError> {
+    let client = bot.client.as_ref();
+    match message_type {
+        "SendFriendMessage" => {
+            let message: obj::SendFriendMessage =
+                parse(message).context("parse SendFriendMessage error")?;
+            let chain = map_send(message.elements)
+                .map_err(|err| NativeError::new(ErrorType::InvalidArgument, err.to_string()))
+                .context("map elements error")?;
+            let receipt = client
+                .send_friend_message(message.target, chain)
+                .await
+                .context("SendFriendMessage error")?;
+            Ok(map_receipt(receipt).encode_to_vec())
+        }
+        "SendGroupMessage" => {
+            let message: obj::SendGroupMessage =
+                parse(message).context("parse SendGroupMessage error")?;
+            let chain = map_send(message.elements)
+                .map_err(|err| NativeError::new(ErrorType::InvalidArgument, err.to_string()))
+                .context("map elements error")?;
+            let receipt = client
+                .send_group_message(message.group_code, chain)
+                .await
+                .context("SendGroupMessage error")?;
+            Ok(map_receipt(receipt).encode_to_vec())
+        }
+        "RecallFriendMessage" => {
+            let message: obj::RecallFriendMessage =
+                parse(message).context("parse RecallFriendMessage error")?;
+            client
+                .recall_friend_message(message.target, message.time, message.seqs, message.rands)
+                .await
+                .context("RecallFriendMessage error")?;
+            Ok(vec![])
+        }
+        "RecallGroupMessage" => {
+            let message: obj::RecallGroupMessage =
+                parse(message).context("parse RecallGroupMessage error")?;
+            client
+                .recall_group_message(message.group_code, message.seqs, message.rands)
+                .await
+                .context("RecallGroupMessage error")?;
+            Ok(vec![])
+        }
+        "SolveFriendRequest" => {
+            let message: obj::SolveFriendRequest =
+                parse(message).context("parse SolveFriendRequest error")?;
+            client
+                .solve_friend_system_message(message.msg_seq, message.req_uin, message.accept)
+                .await
+                .context("SolveFriendRequest error")?;
+            Ok(vec![])
+        }
+        "SolveGroupRequest" => {
+            let message: obj::SolveGroupRequest =
+                parse(message).context("parse SolveGroupRequest error")?;
+            client
+                .solve_group_system_message(
+                    message.msg_seq,
+                    message.req_uin,
+                    message.group_code,
+                    message.suspicious,
+                    false,
+                    message.accept,
+                    message.block,
+                    message.reason,
+                )
+                .await
+                .context("SolveGroupRequest error")?;
+            Ok(vec![])
+        }
+        "SolveSelfInvited" => {
+            let message: obj::SolveSelfInvited =
+                parse(message).context("parse SolveSelfInvited error")?;
+            client
+                .solve_group_system_message(
+                    message.msg_seq,
+                    message.invitor_uin,
+                    message.group_code,
+                    false,
+                    true,
+                    message.accept,
+                    false,
+                    String::new(),
+                )
+                .await
+                .context("SolveSelfInvited error")?;
+            Ok(vec![])
+        }
+        "GroupMuteMember" => {
+            let message: obj::GroupMuteMember =
+                parse(message).context("parse GroupMuteMember error")?;
+            if message.duration < 0 {
+                return Err(NativeError::new(
+                    ErrorType::InvalidArgument,
+                    "duration must not be negative",
+                ));
+            }
+            require_group_permission(client, message.group_code, false)
+                .await
+                .context("GroupMuteMember error")?;
+            client
+                .group_mute(
+                    message.group_code,
+                    message.member_uin,
+                    Duration::from_secs(message.duration as u64),
+                )
+                .await
+                .context("GroupMuteMember error")?;
+            Ok(vec![])
+        }
+        "GroupMuteAll" => {
+            let message: obj::GroupMuteAll = parse(message).context("parse GroupMuteAll error")?;
+            require_group_permission(client, message.group_code, false)
+                .await
+                .context("GroupMuteAll error")?;
+            client
+                .group_mute_all(message.group_code, message.mute)
+                .await
+                .context("GroupMuteAll error")?;
+            Ok(vec![])
+        }
+        "GroupKick" => {
+            let message: obj::GroupKick = parse(message).context("parse GroupKick error")?;
+            require_group_permission(client, message.group_code, false)
+                .await
+                .context("GroupKick error")?;
+            client
+                .group_kick(
+                    message.group_code,
+                    message.member_uins,
+                    message.kick_msg.as_str(),
+                    message.block,
+                )
+                .await
+                .context("GroupKick error")?;
+            Ok(vec![])
+        }
+        "GroupSetAdmin" => {
+            let message: obj::GroupSetAdmin =
+                parse(message).context("parse GroupSetAdmin error")?;
+            require_group_permission(client, message.group_code, true)
+                .await
+                .context("GroupSetAdmin error")?;
+            client
+                .group_set_admin(message.group_code, message.member_uin, message.admin)
+                .await
+                .context("GroupSetAdmin error")?;
+            Ok(vec![])
+        }
+        "GroupEditCard" => {
+            let message: obj::GroupEditCard =
+                parse(message).context("parse GroupEditCard error")?;
+            // 修改自己的群名片不需要管理员权限
+            if message.member_uin != client.uin().await {
+                require_group_permission(client, message.group_code, false)
+                    .await
+                    .context("GroupEditCard error")?;
+            }
+            client
+                .edit_group_member_card(message.group_code, message.member_uin, message.card)
+                .await
+                .context("GroupEditCard error")?;
+            Ok(vec![])
+        }
+        "GroupEditSpecialTitle" => {
+            let message: obj::GroupEditSpecialTitle =
+                parse(message).context("parse GroupEditSpecialTitle error")?;
+            require_group_permission(client, message.group_code, true)
+                .await
+                .context("GroupEditSpecialTitle error")?;
+            client
+                .group_edit_special_title(message.group_code, message.member_uin, message.title)
+                .await
+                .context("GroupEditSpecialTitle error")?;
+            Ok(vec![])
+        }
+        "GetFriendList" => {
+            let rsp = client
+                .get_friend_list()
+                .await
+                .context("GetFriendList error")?;
+            Ok(obj::FriendList {
+                friends: rsp.friends.into_iter().map(map_friend_info).collect(),
+            }
+            .encode_to_vec())
+        }
+        "GetGroupList" => {
+            let groups = client
+                .get_group_list()
+                .await
+                .context("GetGroupList error")?;
+            Ok(obj::GroupList {
+                groups: groups.into_iter().map(map_group_info).collect(),
+            }
+            .encode_to_vec())
+        }
+        "GetGroupMemberList" => {
+            let message: obj::GetGroupMemberList =
+                parse(message).context("parse GetGroupMemberList error")?;
+            // 获取成员列表需要群主uin
+            let group = client
+                .get_group_info(message.group_code)
+                .await
+                .context("GetGroupMemberList error")?
+                .ok_or_else(|| NativeError::new(ErrorType::NotFound, "group not found"))?;
+            let members = client
+                .get_group_member_list(message.group_code, group.owner_uin)
+                .await
+                .context("GetGroupMemberList error")?;
+            Ok(obj::GroupMemberList {
+                members: members.into_iter().map(map_group_member_info).collect(),
+            }
+            .encode_to_vec())
+        }
+        "UploadImage" => {
+            let message: obj::UploadImageDto =
+                parse(message).context("parse UploadImageDto error")?;
+            if message.target_type == obj::enums::SendTargetType::Friend as i32 {
+                let img = client
+                    .upload_friend_image(message.target, message.data.as_slice())
+                    .await
+                    .context("UploadImage error")?;
+                Ok(map_friend_image(img, false).encode_to_vec())
+            } else if message.target_type == obj::enums::SendTargetType::Group as i32 {
+                let img = client
+                    .upload_group_image(message.target, message.data.as_slice())
+                    .await
+                    .context("UploadImage error")?;
+                Ok(map_group_image(img, false).encode_to_vec())
+            } else {
+                Err(NativeError::new(
+                    ErrorType::InvalidArgument,
+                    "unknown target type",
+                ))
+            }
+        }
+        "RequestSms" => {
+            bot.login
+                .submit_device_lock(DeviceLockAction::RequestSms)
+                .context("RequestSms error")?;
+            Ok(vec![])
+        }
+        "SubmitSmsCode" => {
+            let message: obj::SubmitSmsCode =
+                parse(message).context("parse SubmitSmsCode error")?;
+            bot.login
+                .submit_device_lock(DeviceLockAction::SubmitSmsCode(message.code))
+                .context("SubmitSmsCode error")?;
+            Ok(vec![])
+        }
+        "ConfirmDeviceLock" => {
+            bot.login
+                .submit_device_lock(DeviceLockAction::Confirm)
+                .context("ConfirmDeviceLock error")?;
+            Ok(vec![])
+        }
+        "SubmitTicket" => {
+            let message: obj::SubmitTicket = parse(message).context("parse SubmitTicket error")?;
+            bot.login
+                .submit_ticket(message.ticket)
+                .context("SubmitTicket error")?;
+            Ok(vec![])
+        }
+        "CancelLogin" => {
+            bot.login.cancel();
+            Ok(vec![])
+        }
+        "GetStatus" => {
+            let uin = client.uin().await;
+            let nickname = client.account_info.read().await.nickname.clone();
+            Ok(bot
+                .status
+                .to_proto(uin, nickname, &bot.events)
+                .encode_to_vec())
+        }
+        "Shutdown" => {
+            bot.lifecycle
+                .shutdown(bot)
+                .await
+                .map_err(|err| NativeError::new(ErrorType::Internal, format!("{err:?}")))
+                .context("Shutdown error")?;
+            Ok(vec![])
+        }
+        "Restart" => {
+            bot.lifecycle
+                .restart(bot)
+                .await
+                .map_err(|err| NativeError::new(ErrorType::Internal, format!("{err:?}")))
+                .context("Restart error")?;
+            Ok(vec![])
+        }
+        _ => Err(NativeError::new(
+            ErrorType::InvalidArgument,
+            format!("unknown message type : {message_type}"),
+        )),
+    }
+}
+
+fn parse<T>(message: Vec<u8>) -> Result<T, NativeError>
+where
+    T: Message + Default,
+{
+    T::decode(message.as_slice())
+        .map_err(|err| NativeError::new(ErrorType::InvalidArgument, err.to_string()))
+}
+
+/// 检查机器人在群中的权限, owner为true时要求群主
+async fn require_group_permission(
+    client: &ricq::Client,
+    group_code: i64,
+    owner: bool,
+) -> Result<(), NativeError> {
+    let uin = client.uin().await;
+    let member = client.get_group_member_info(group_code, uin).await?;
+    match member.permission {
+        GroupMemberPermission::Owner => Ok(()),
+        GroupMemberPermission::Administrator if !owner => Ok(()),
+        _ if owner => Err(NativeError::new(
+            ErrorType::NotGroupOwner,
+            "not group owner",
+        )),
+        _ => Err(NativeError::new(
+            ErrorType::NotGroupAdmin,
+            "not group admin",
+        )),
+    }
+}
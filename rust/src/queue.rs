@@ -0,0 +1,307 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+use crate::event::{self, BotEvent};
+use crate::obj;
+use crate::obj::enums::OverflowStrategy;
+
+/// 已经转换为Java事件的数据, name为Java事件类名
+pub(crate) struct QueuedEvent {
+    pub name: &'static str,
+    pub data: Vec<u8>,
+    seq: u64,
+}
+
+/// ricq和登录流程产生的事件在这里等待分发给Java, 队列满时按照配置的策略阻塞或丢弃
+///
+/// 队列按会话分片, 每个分片由一个分发线程按顺序取出, 同一会话的事件总是在同一个分片中
+///
+/// Block只暂停放入事件的任务, ricq为每个数据包启动任务, 等待中的任务和数据包不受capacity限制,
+/// Java持续处理不过来时内存仍会增长, 需要限制内存时使用DropOldest或DropByType
+pub(crate) struct EventQueue {
+    capacity: usize,
+    overflow: OverflowStrategy,
+    droppable: HashSet<String>,
+    state: Mutex<QueueState>,
+    not_empty: Vec<Notify>,
+    not_full: Notify,
+}
+
+#[derive(Default)]
+struct QueueState {
+    shards: Vec<VecDeque<QueuedEvent>>,
+    len: usize,
+    seq: u64,
+    closed: bool,
+    dropped: HashMap<&'static str, i64>,
+}
+
+impl EventQueue {
+    pub(crate) fn new(config: &obj::EventQueueConfig, shards: usize) -> Self {
+        Self {
+            capacity: config.capacity as usize,
+            overflow: OverflowStrategy::from_i32(config.overflow)
+                .unwrap_or(OverflowStrategy::Block),
+            droppable: config.droppable_events.iter().cloned().collect(),
+            state: Mutex::new(QueueState {
+                shards: (0..shards).map(|_| VecDeque::new()).collect(),
+                ..Default::default()
+            }),
+            not_empty: (0..shards).map(|_| Notify::new()).collect(),
+            not_full: Notify::new(),
+        }
+    }
+
+    pub(crate) fn shards(&self) -> usize {
+        self.not_empty.len()
+    }
+
+    /// 不需要传递给Java的事件直接忽略, 策略为Block时等待队列有空位, bot_uin在登录完成前为0
+    pub(crate) async fn push(&self, event: BotEvent, bot_uin: i64) {
+        tracing::trace!("event : {:?}", event);
+        let conversation = event::conversation(&event, bot_uin);
+        if let Some((name, data)) = event::map_event(event) {
+            self.push_mapped(name, data, conversation).await;
+        }
+    }
+
+    /// 放入已经转换好的Java事件, conversation相同的事件按放入的顺序分发
+    pub(crate) async fn push_mapped(&self, name: &'static str, data: Vec<u8>, conversation: i64) {
+        let shard = conversation.unsigned_abs() as usize % self.shards();
+        let mut data = Some(data);
+        loop {
+            let not_full = self.not_full.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.closed {
+                    tracing::warn!("event dropped, dispatch loop closed : {}", name);
+                    return;
+                }
+                if state.len >= self.capacity && !self.make_room(&mut state, name) {
+                    if self.overflow == OverflowStrategy::DropByType
+                        && self.droppable.contains(name)
+                    {
+                        *state.dropped.entry(name).or_default() += 1;
+                        return;
+                    }
+                    // 没有可以丢弃的事件, 等待Java处理
+                } else {
+                    state.seq += 1;
+                    let event = QueuedEvent {
+                        name,
+                        data: data.take().unwrap(),
+                        seq: state.seq,
+                    };
+                    state.shards[shard].push_back(event);
+                    state.len += 1;
+                    drop(state);
+                    self.not_empty[shard].notify_one();
+                    return;
+                }
+            }
+            not_full.await;
+        }
+    }
+
+    /// 按照策略丢弃事件腾出位置, 返回新事件是否可以入队
+    fn make_room(&self, state: &mut QueueState, name: &'static str) -> bool {
+        let found = match self.overflow {
+            OverflowStrategy::Block => return false,
+            // 各分片队首中最早入队的事件
+            OverflowStrategy::DropOldest => state
+                .shards
+                .iter()
+                .enumerate()
+                .filter_map(|(shard, events)| events.front().map(|e| (e.seq, shard, 0)))
+                .min(),
+            OverflowStrategy::DropByType => {
+                if self.droppable.contains(name) {
+                    return false;
+                }
+                state
+                    .shards
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(shard, events)| {
+                        events
+                            .iter()
+                            .position(|e| self.droppable.contains(e.name))
+                            .map(|index| (events[index].seq, shard, index))
+                    })
+                    .min()
+            }
+        };
+        let dropped = found.and_then(|(_, shard, index)| state.shards[shard].remove(index));
+        match dropped {
+            Some(dropped) => {
+                state.len -= 1;
+                *state.dropped.entry(dropped.name).or_default() += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 取出分片中的下一个事件, 队列关闭并且分片为空时返回None
+    pub(crate) async fn pop(&self, shard: usize) -> Option<QueuedEvent> {
+        loop {
+            let not_empty = self.not_empty[shard].notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if let Some(event) = state.shards[shard].pop_front() {
+                    state.len -= 1;
+                    drop(state);
+                    self.not_full.notify_waiters();
+                    return Some(event);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            not_empty.await;
+        }
+    }
+
+    /// 不再接收新事件, 已经在队列中的事件仍然可以取出
+    pub(crate) fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.not_full.notify_waiters();
+        for not_empty in &self.not_empty {
+            not_empty.notify_one();
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.state.lock().unwrap().len
+    }
+
+    /// 每种事件被丢弃的数量
+    pub(crate) fn dropped(&self) -> HashMap<String, i64> {
+        self.state
+            .lock()
+            .unwrap()
+            .dropped
+            .iter()
+            .map(|(name, count)| (name.to_string(), *count))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::Poll;
+
+    fn queue(
+        capacity: i32,
+        overflow: OverflowStrategy,
+        droppable: &[&str],
+        shards: usize,
+    ) -> EventQueue {
+        let config = obj::EventQueueConfig {
+            capacity,
+            overflow: overflow as i32,
+            droppable_events: droppable.iter().map(|name| name.to_string()).collect(),
+        };
+        EventQueue::new(&config, shards)
+    }
+
+    async fn pop_data(queue: &EventQueue, shard: usize) -> u8 {
+        queue.pop(shard).await.unwrap().data[0]
+    }
+
+    /// poll一次, 返回是否已经完成
+    async fn poll_once(future: &mut Pin<&mut impl Future<Output = ()>>) -> bool {
+        std::future::poll_fn(|cx| Poll::Ready(future.as_mut().poll(cx).is_ready())).await
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_across_shards() {
+        let queue = queue(3, OverflowStrategy::DropOldest, &[], 2);
+        queue.push_mapped("A", vec![1], 0).await;
+        queue.push_mapped("B", vec![2], 1).await;
+        queue.push_mapped("C", vec![3], 1).await;
+        // 最早的是分片0的A
+        queue.push_mapped("D", vec![4], 0).await;
+        // 分片0的队首D晚于分片1的队首B
+        queue.push_mapped("E", vec![5], 1).await;
+        assert_eq!(queue.len(), 3);
+        assert_eq!(pop_data(&queue, 0).await, 4);
+        assert_eq!(pop_data(&queue, 1).await, 3);
+        assert_eq!(pop_data(&queue, 1).await, 5);
+        let dropped = queue.dropped();
+        assert_eq!(dropped.len(), 2);
+        assert_eq!(dropped["A"], 1);
+        assert_eq!(dropped["B"], 1);
+    }
+
+    #[tokio::test]
+    async fn drop_by_type() {
+        let queue = queue(2, OverflowStrategy::DropByType, &["GroupMessageEvent"], 1);
+        queue.push_mapped("GroupMessageEvent", vec![1], 0).await;
+        queue.push_mapped("FriendMessageEvent", vec![2], 0).await;
+        // 丢弃队列中的群消息给好友消息腾出位置
+        queue.push_mapped("FriendMessageEvent", vec![3], 0).await;
+        // 队列中没有群消息, 新的群消息直接丢弃
+        queue.push_mapped("GroupMessageEvent", vec![4], 0).await;
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.dropped()["GroupMessageEvent"], 2);
+        // 没有可以丢弃的事件时等待
+        let producer = queue.push_mapped("FriendMessageEvent", vec![5], 0);
+        tokio::pin!(producer);
+        assert!(!poll_once(&mut producer).await);
+        assert_eq!(pop_data(&queue, 0).await, 2);
+        assert!(poll_once(&mut producer).await);
+        assert_eq!(pop_data(&queue, 0).await, 3);
+        assert_eq!(pop_data(&queue, 0).await, 5);
+        assert_eq!(queue.dropped().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn block_until_pop() {
+        let queue = queue(1, OverflowStrategy::Block, &[], 1);
+        queue.push_mapped("A", vec![1], 0).await;
+        let producer = queue.push_mapped("B", vec![2], 0);
+        tokio::pin!(producer);
+        assert!(!poll_once(&mut producer).await);
+        assert_eq!(pop_data(&queue, 0).await, 1);
+        assert!(poll_once(&mut producer).await);
+        assert_eq!(pop_data(&queue, 0).await, 2);
+        assert!(queue.dropped().is_empty());
+    }
+
+    #[tokio::test]
+    async fn close_releases_producers() {
+        let queue = queue(1, OverflowStrategy::Block, &[], 1);
+        queue.push_mapped("A", vec![1], 0).await;
+        let producer = queue.push_mapped("B", vec![2], 0);
+        tokio::pin!(producer);
+        assert!(!poll_once(&mut producer).await);
+        queue.close();
+        assert!(poll_once(&mut producer).await);
+        // 关闭前的事件仍然可以取出, 等待中的事件被丢弃
+        assert_eq!(pop_data(&queue, 0).await, 1);
+        assert!(queue.pop(0).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn shard_by_conversation() {
+        let queue = queue(100, OverflowStrategy::Block, &[], 4);
+        for (data, conversation) in [(1, 1), (2, 5), (3, -3), (4, 0), (5, 1)] {
+            queue.push_mapped("A", vec![data], conversation).await;
+        }
+        queue.close();
+        assert_eq!(pop_data(&queue, 0).await, 4);
+        // 同一分片中按放入的顺序
+        assert_eq!(pop_data(&queue, 1).await, 1);
+        assert_eq!(pop_data(&queue, 1).await, 2);
+        assert_eq!(pop_data(&queue, 1).await, 5);
+        assert_eq!(pop_data(&queue, 3).await, 3);
+        for shard in 0..4 {
+            assert!(queue.pop(shard).await.is_none());
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use rand::Rng;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::obj;
+
+/// 重连间隔, 第n次为 initial_delay * multiplier^(n-1), 最多 max_delay, 再随机增减 jitter 的比例
+pub(crate) struct Backoff<'a> {
+    config: &'a obj::ReconnectConfig,
+    attempt: i32,
+}
+
+impl<'a> Backoff<'a> {
+    pub(crate) fn new(config: &'a obj::ReconnectConfig) -> Self {
+        Self { config, attempt: 0 }
+    }
+
+    /// 超过最大次数时返回None
+    pub(crate) fn next_delay(&mut self) -> Option<Duration> {
+        if self.config.max_attempts > 0 && self.attempt >= self.config.max_attempts {
+            return None;
+        }
+        self.attempt += 1;
+        let delay = (self.config.initial_delay as f64
+            * self.config.multiplier.powi(self.attempt - 1))
+        .min(self.config.max_delay as f64);
+        let jitter = if self.config.jitter > 0.0 {
+            rand::thread_rng().gen_range(-self.config.jitter..=self.config.jitter)
+        } else {
+            0.0
+        };
+        Some(Duration::from_millis((delay * (1.0 + jitter)) as u64))
+    }
+
+    pub(crate) fn attempt(&self) -> i32 {
+        self.attempt
+    }
+}
+
+/// 轮换使用服务器地址, 连接失败的地址在 blacklist_duration 内不再使用
+pub(crate) struct AddressPool {
+    blacklist: HashMap<SocketAddr, Instant>,
+    duration: Duration,
+    offset: usize,
+}
+
+impl AddressPool {
+    pub(crate) fn new(config: &obj::ReconnectConfig) -> Self {
+        Self {
+            blacklist: HashMap::new(),
+            duration: Duration::from_millis(config.blacklist_duration as u64),
+            offset: rand::thread_rng().gen::<u16>() as usize,
+        }
+    }
+
+    /// 按轮换后的顺序返回可用的地址, 全部被拉黑时忽略黑名单
+    pub(crate) fn candidates(&mut self, mut addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        if addresses.is_empty() {
+            return addresses;
+        }
+        let now = Instant::now();
+        self.blacklist.retain(|_, until| *until > now);
+        let len = addresses.len();
+        addresses.rotate_left(self.offset % len);
+        self.offset = self.offset.wrapping_add(1);
+        let available: Vec<SocketAddr> = addresses
+            .iter()
+            .filter(|address| !self.blacklist.contains_key(address))
+            .copied()
+            .collect();
+        if available.is_empty() {
+            addresses
+        } else {
+            available
+        }
+    }
+
+    pub(crate) fn block(&mut self, address: SocketAddr) {
+        if !self.duration.is_zero() {
+            tracing::info!("{}毫秒内不再使用 {}", self.duration.as_millis(), address);
+            self.blacklist
+                .insert(address, Instant::now() + self.duration);
+        }
+    }
+}
@@ -1,122 +1,335 @@
 use anyhow::{anyhow, Context, Result};
 use bytes::Bytes;
-use bytes::BytesMut;
-use bytes::{Buf, BufMut};
-use rand::seq::IteratorRandom;
-use ricq::client::Token;
+use ricq::client::{NetworkStatus, Token};
 use ricq::ext::common::after_login;
-use ricq::handler::QEvent;
 use ricq::{
     LoginDeviceLocked, LoginNeedCaptcha, LoginResponse, LoginSuccess, LoginUnknownStatus,
     QRCodeConfirmed, QRCodeImageFetch, QRCodeState, RQError, RQResult,
 };
-use ricq_core::binary::BinaryReader;
-use ricq_core::binary::BinaryWriter;
-use std::cmp::min;
-use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::net::TcpStream;
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::{oneshot, Notify};
 use tokio::task::JoinHandle;
-use tokio::time::sleep;
+use tokio::time::{sleep, timeout};
 
-pub(crate) async fn run_ricq(
-    c: Arc<ricq::Client>,
-    _sender: Arc<UnboundedSender<QEvent>>,
-) -> Result<()> {
+use crate::captcha::Captcha;
+use crate::config;
+use crate::error::NativeError;
+use crate::event::BotEvent;
+use crate::obj;
+use crate::obj::enums::{ConnectionState, ErrorType, LoginMethod};
+use crate::reconnect::{AddressPool, Backoff};
+use crate::session::Bot;
+use crate::token::{bytes_to_token, token_to_bytes};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+pub(crate) async fn run_ricq(bot: Arc<Bot>, config: obj::RijqConfig) -> Result<()> {
     tracing::info!("开始运行客户端");
+    let login_config = config.login.clone().unwrap_or_default();
+    let reconnect = config::reconnect_config(&config);
+    let c = bot.client.clone();
+    let mut addresses = AddressPool::new(&reconnect);
+    bot.status.set_state(ConnectionState::Connecting);
     // 连接到服务器
-    let mut handle = connection(c.clone()).await?;
+    let mut handle = connection(&c, &mut addresses).await?;
     // 让步
     tokio::task::yield_now().await;
     sleep(Duration::from_secs(1)).await;
     // 连接成功
     tracing::info!("已连接到服务器");
+    bot.status.set_state(ConnectionState::Connected);
     // 优先使用token登录
-    if !token_login(c.as_ref()).await {
-        tracing::info!("未能使用token登录，进行扫码");
-        qr_login(c.clone()).await?;
-        write_token_to_store(c.gen_token().await).await?;
+    let restored = token_login(&bot).await.unwrap_or_else(|err| {
+        tracing::warn!("token登录出错 : {:?}", err);
+        false
+    });
+    if !restored {
+        tracing::info!("未能使用token登录，使用配置的方式登录");
+        login(&bot, &login_config).await?;
+        write_token_to_store(&bot, c.gen_token().await).await?;
     }
+    bot.status.set_uin(c.uin().await);
+    bot.status.set_state(ConnectionState::Online);
     loop {
         // 每次轮询d
         after_login(&c.clone()).await;
         // 直到连接断开
         tracing::info!("开始接收消息");
-        let err = match handle.await {
-            Ok(_) => {
-                tracing::warn!("连接已断开");
-                anyhow::Error::msg("what's up")
-            }
-            Err(err) => {
-                tracing::warn!("连接已断开 {:?}", err);
-                err.into()
+        let probe = AbortOnDrop(tokio::spawn(probe_heartbeat(bot.clone())));
+        let reason = match handle.await {
+            Ok(_) => "连接已断开".to_string(),
+            Err(err) => err.to_string(),
+        };
+        drop(probe);
+        tracing::warn!("连接已断开 : {}", reason);
+        bot.status.set_state(ConnectionState::Reconnecting);
+        bot.emit(BotEvent::Disconnected(obj::DisconnectedEvent {
+            reason,
+            stopped: false,
+        }))
+        .await;
+        // 恢复会话之前的重连共用次数限制和间隔
+        let mut backoff = Backoff::new(&reconnect);
+        handle = loop {
+            let h = re_connection(&bot, &mut addresses, &mut backoff).await?;
+            // 让步
+            tokio::task::yield_now().await;
+            sleep(Duration::from_secs(1)).await;
+            // 连接成功
+            tracing::info!("恢复连接");
+            bot.status.set_state(ConnectionState::Connected);
+            match token_login(&bot).await {
+                Ok(true) => {
+                    tracing::info!("恢复会话");
+                    break h;
+                }
+                Ok(false) => {
+                    // 会话失效时重新走一遍登录流程, 而不是让机器人停在未登录的状态
+                    tracing::warn!("未能恢复会话, 使用配置的方式重新登录");
+                    bot.emit(BotEvent::SessionLost(obj::SessionLostEvent {
+                        reason: "未能使用token恢复会话".to_string(),
+                    }))
+                    .await;
+                    login(&bot, &login_config).await?;
+                    write_token_to_store(&bot, c.gen_token().await).await?;
+                    break h;
+                }
+                Err(err) => {
+                    // 网络错误时会话可能仍然有效, 断开后重新连接
+                    tracing::warn!("恢复会话时出错, 重新连接 : {:?}", err);
+                    c.stop(NetworkStatus::NetworkOffline);
+                    let _ = h.await;
+                    bot.status.set_state(ConnectionState::Reconnecting);
+                }
             }
         };
-        handle = re_connection(c.clone()).await?;
-        // 让步
-        tokio::task::yield_now().await;
-        sleep(Duration::from_secs(1)).await;
-        // 连接成功
-        tracing::info!("恢复连接");
-        if token_login(c.as_ref()).await {
-            tracing::info!("恢复会话");
-        } else {
-            tracing::warn!("未能恢复会话");
-            return Err(err);
+        bot.status.set_uin(c.uin().await);
+        bot.status.set_state(ConnectionState::Online);
+        // 会话恢复后才算重连成功
+        bot.status.reconnected();
+        bot.emit(BotEvent::Reconnected(obj::ReconnectedEvent {
+            attempts: backoff.attempt(),
+        }))
+        .await;
+    }
+}
+
+/// run_ricq被停止或重启abort时, 随之结束它启动的任务
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// 一段时间没有收到服务器推送的事件时主动发送心跳, 成功时记录活动时间
+async fn probe_heartbeat(bot: Arc<Bot>) {
+    loop {
+        sleep(HEARTBEAT_INTERVAL).await;
+        if bot.status.idle_millis() < HEARTBEAT_INTERVAL.as_millis() as i64 {
+            continue;
+        }
+        match bot.client.heartbeat().await {
+            Ok(_) => bot.status.record_activity(),
+            Err(err) => tracing::warn!("心跳失败 : {:?}", err),
         }
     }
 }
 
-async fn connection(client: Arc<ricq::Client>) -> Result<JoinHandle<()>> {
-    let addresses = client.get_address_list().await;
-    let address = addresses
-        .into_iter()
-        .choose_stable(&mut rand::thread_rng())
-        .unwrap();
-    let conn = TcpStream::connect(address)
-        .await
-        .with_context(|| "连接到服务器出错")?;
-    Ok(tokio::spawn(async move { client.start(conn).await }))
+/// 按轮换顺序尝试可用的地址, 失败的地址加入黑名单
+async fn connection(
+    client: &Arc<ricq::Client>,
+    addresses: &mut AddressPool,
+) -> Result<JoinHandle<()>> {
+    let candidates = addresses.candidates(client.get_address_list().await);
+    if candidates.is_empty() {
+        return Err(anyhow!("没有可用的服务器地址"));
+    }
+    for address in candidates {
+        match timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await {
+            Ok(Ok(conn)) => {
+                tracing::info!("连接到 {}", address);
+                let client = client.clone();
+                return Ok(tokio::spawn(async move { client.start(conn).await }));
+            }
+            Ok(Err(err)) => tracing::warn!("连接到 {} 出错 : {:?}", address, err),
+            Err(_) => tracing::warn!("连接到 {} 超时", address),
+        }
+        addresses.block(address);
+    }
+    Err(anyhow!("连接到服务器出错"))
 }
 
-async fn token_login(client: &ricq::Client) -> bool {
-    let session_file = FileSessionStore::boxed("rijq.session");
-    let session_data = match session_file.load_session().await {
+/// 没有会话或者会话失效时返回false, 其他错误 (例如网络错误) 返回Err
+async fn token_login(bot: &Bot) -> Result<bool> {
+    let client = bot.client.as_ref();
+    let session_store = bot.session_store.as_ref();
+    let session_data = match session_store.load_session().await {
         Ok(data) => data,
         Err(err) => {
             tracing::info!("{:?}", err);
-            return false;
+            return Ok(false);
         }
     };
     if let Some(session_data) = session_data {
-        let result = client.token_login(bytes_to_token(session_data)).await;
+        let token = match bytes_to_token(&session_data) {
+            Ok(token) => token,
+            Err(err) => {
+                tracing::warn!("会话已损坏, 使用配置的方式重新登录 : {:?}", err);
+                let _ = session_store.remove_session().await;
+                return Ok(false);
+            }
+        };
+        let result = client.token_login(token).await;
         match result {
-            Ok(_) => true,
+            Ok(_) => Ok(true),
             Err(err) => match err {
                 RQError::TokenLoginFailed => {
                     // token error (KickedOffline)
-                    let _ = session_file.remove_session().await;
-                    false
+                    let _ = session_store.remove_session().await;
+                    Ok(false)
                 }
-                _ => false,
+                err => Err(err.into()),
             },
         }
     } else {
-        false
+        Ok(false)
     }
 }
 
-async fn write_token_to_store(token: Token) -> Result<()> {
-    let session_file = FileSessionStore::boxed("rijq.session");
-    session_file
-        .as_ref()
+pub(crate) async fn write_token_to_store(bot: &Bot, token: Token) -> Result<()> {
+    bot.session_store
         .save_session(token_to_bytes(&token).to_vec())
         .await
 }
 
-async fn qr_login(rq_client: Arc<ricq::Client>) -> Result<()> {
+/// 设备锁时Java侧可以进行的操作
+pub(crate) enum DeviceLockAction {
+    RequestSms,
+    SubmitSmsCode(String),
+    /// 已经通过验证地址完成验证, 重新登录
+    Confirm,
+}
+
+enum LoginWaiter {
+    DeviceLock(oneshot::Sender<DeviceLockAction>),
+    Captcha(oneshot::Sender<String>),
+}
+
+/// 登录流程等待Java侧操作的位置, 同一时间只有一个等待者
+#[derive(Default)]
+pub(crate) struct LoginInteraction {
+    waiting: Mutex<Option<LoginWaiter>>,
+    cancelled: Notify,
+}
+
+impl LoginInteraction {
+    async fn wait_device_lock(&self) -> Result<DeviceLockAction> {
+        let (sender, receiver) = oneshot::channel();
+        *self.waiting.lock().unwrap() = Some(LoginWaiter::DeviceLock(sender));
+        receiver.await.with_context(|| "登录等待已取消")
+    }
+
+    /// 立即注册等待者, 之后提交的ticket从返回的receiver中取得
+    pub(crate) fn expect_ticket(&self) -> oneshot::Receiver<String> {
+        let (sender, receiver) = oneshot::channel();
+        *self.waiting.lock().unwrap() = Some(LoginWaiter::Captcha(sender));
+        receiver
+    }
+
+    pub(crate) fn clear(&self) {
+        self.waiting.lock().unwrap().take();
+    }
+
+    /// 等待Java侧取消登录
+    pub(crate) async fn cancelled(&self) {
+        self.cancelled.notified().await
+    }
+
+    /// 取消正在等待的登录操作, 登录流程会以错误结束
+    pub(crate) fn cancel(&self) {
+        self.clear();
+        self.cancelled.notify_waiters();
+    }
+
+    pub(crate) fn submit_device_lock(&self, action: DeviceLockAction) -> Result<(), NativeError> {
+        let mut waiting = self.waiting.lock().unwrap();
+        match waiting.take() {
+            Some(LoginWaiter::DeviceLock(sender)) => sender
+                .send(action)
+                .map_err(|_| NativeError::new(ErrorType::IllegalState, "login flow has ended")),
+            other => {
+                *waiting = other;
+                Err(NativeError::new(
+                    ErrorType::IllegalState,
+                    "login flow is not waiting for device lock",
+                ))
+            }
+        }
+    }
+
+    pub(crate) fn submit_ticket(&self, ticket: String) -> Result<(), NativeError> {
+        let mut waiting = self.waiting.lock().unwrap();
+        match waiting.take() {
+            Some(LoginWaiter::Captcha(sender)) => sender
+                .send(ticket)
+                .map_err(|_| NativeError::new(ErrorType::IllegalState, "login flow has ended")),
+            other => {
+                *waiting = other;
+                Err(NativeError::new(
+                    ErrorType::IllegalState,
+                    "login flow is not waiting for captcha",
+                ))
+            }
+        }
+    }
+}
+
+/// 一次登录尝试的结果
+enum LoginFlow {
+    Success,
+    /// 设备锁验证完成, 需要重新发起登录
+    Retry,
+}
+
+async fn login(bot: &Bot, config: &obj::LoginConfig) -> Result<()> {
+    let captcha = Captcha::from_config(config)?;
+    loop {
+        let flow = match LoginMethod::from_i32(config.method) {
+            Some(LoginMethod::Password) => {
+                tracing::info!("使用密码登录 : {}", config.uin);
+                let first = bot
+                    .client
+                    .password_login(config.uin, config.password.as_str())
+                    .await;
+                loop_login(bot, &captcha, first).await?
+            }
+            Some(LoginMethod::PasswordMd5) => {
+                tracing::info!("使用MD5密码登录 : {}", config.uin);
+                let first = bot
+                    .client
+                    .password_md5_login(config.uin, config.password_md5.as_slice())
+                    .await;
+                loop_login(bot, &captcha, first).await?
+            }
+            _ => {
+                tracing::info!("进行扫码登录");
+                qr_login(bot, &captcha).await?
+            }
+        };
+        match flow {
+            LoginFlow::Success => return Ok(()),
+            LoginFlow::Retry => tracing::info!("设备锁验证完成, 重新登录"),
+        }
+    }
+}
+
+async fn qr_login(bot: &Bot, captcha: &Captcha) -> Result<LoginFlow> {
+    let rq_client = bot.client.as_ref();
     let mut image_sig = Bytes::new();
     let mut resp = rq_client
         .fetch_qrcode()
@@ -130,8 +343,22 @@ async fn qr_login(rq_client: Arc<ricq::Client>) -> Result<()> {
                 ref sig,
             }) => {
                 image_sig = sig.clone();
-                if let Err(err) = print_qr_to_console(image_data) {
-                    return Err(anyhow!("二维码打印到控制台时出现误 : {}", err));
+                let url = match decode_qr(image_data) {
+                    Ok(url) => url,
+                    Err(err) => {
+                        tracing::warn!("二维码识别失败 : {}", err);
+                        String::new()
+                    }
+                };
+                bot.emit(BotEvent::LoginQrCode(obj::LoginQrCodeEvent {
+                    image: image_data.to_vec(),
+                    url: url.clone(),
+                }))
+                .await;
+                if !url.is_empty() {
+                    if let Err(err) = qr2term::print_qr(url.as_str()) {
+                        tracing::warn!("二维码打印到控制台时出现错误 : {}", err);
+                    }
                 }
                 tracing::info!("请扫码");
             }
@@ -159,7 +386,7 @@ async fn qr_login(rq_client: Arc<ricq::Client>) -> Result<()> {
                 let first = rq_client
                     .qrcode_login(tmp_pwd, tmp_no_pic_sig, tgt_qr)
                     .await;
-                return loop_login(rq_client, first).await;
+                return loop_login(bot, captcha, first).await;
             }
             QRCodeState::Canceled => {
                 return Err(anyhow::Error::msg("二维码已取消"));
@@ -173,43 +400,12 @@ async fn qr_login(rq_client: Arc<ricq::Client>) -> Result<()> {
     }
 }
 
-#[async_trait::async_trait]
-pub trait SessionStore {
-    async fn save_session(&self, data: Vec<u8>) -> Result<()>;
-    async fn load_session(&self) -> Result<Option<Vec<u8>>>;
-    async fn remove_session(&self) -> Result<()>;
-}
-
-pub struct FileSessionStore {
-    pub path: String,
-}
-
-impl FileSessionStore {
-    pub fn boxed(path: impl Into<String>) -> Box<dyn SessionStore + Send + Sync> {
-        return Box::new(Self { path: path.into() });
-    }
-}
-
-#[async_trait::async_trait]
-impl SessionStore for FileSessionStore {
-    async fn save_session(&self, data: Vec<u8>) -> Result<()> {
-        tokio::fs::write(self.path.as_str(), data).await?;
-        Ok(())
-    }
-    async fn load_session(&self) -> Result<Option<Vec<u8>>> {
-        if Path::new(self.path.as_str()).exists() {
-            Ok(Some(tokio::fs::read(self.path.as_str()).await?))
-        } else {
-            Ok(None)
-        }
-    }
-    async fn remove_session(&self) -> Result<()> {
-        let _ = tokio::fs::remove_file(self.path.as_str()).await;
-        Ok(())
-    }
-}
-
-async fn loop_login(rq_client: Arc<ricq::Client>, first: RQResult<LoginResponse>) -> Result<()> {
+async fn loop_login(
+    bot: &Bot,
+    captcha: &Captcha,
+    first: RQResult<LoginResponse>,
+) -> Result<LoginFlow> {
+    let rq_client = bot.client.as_ref();
     // netwotrk error
     let mut resp = first?;
     loop {
@@ -218,7 +414,7 @@ async fn loop_login(rq_client: Arc<ricq::Client>, first: RQResult<LoginResponse>
                 ref account_info, ..
             }) => {
                 tracing::info!("登录成功: {:?}", account_info);
-                return Ok(());
+                return Ok(LoginFlow::Success);
             }
             LoginResponse::DeviceLocked(LoginDeviceLocked {
                 ref sms_phone,
@@ -229,15 +425,42 @@ async fn loop_login(rq_client: Arc<ricq::Client>, first: RQResult<LoginResponse>
                 tracing::info!("设备锁 : {:?}", message);
                 tracing::info!("密保手机 : {:?}", sms_phone);
                 tracing::info!("验证地址 : {:?}", verify_url);
-                qr2term::print_qr(
-                    verify_url
-                        .clone()
-                        .with_context(|| "未能取得设备锁验证地址")?
-                        .as_str(),
-                )?;
-                tracing::info!("验证地址 : {:?}", verify_url);
-                tracing::info!("手机扫码或者打开url，处理完成后重启程序");
-                std::process::exit(0);
+                bot.emit(BotEvent::LoginDeviceLock(obj::LoginDeviceLockEvent {
+                    message: message.clone().unwrap_or_default(),
+                    sms_phone: sms_phone.clone().unwrap_or_default(),
+                    verify_url: verify_url.clone().unwrap_or_default(),
+                }))
+                .await;
+                if let Some(verify_url) = verify_url {
+                    if let Err(err) = qr2term::print_qr(verify_url.as_str()) {
+                        tracing::warn!("验证地址打印到控制台时出现错误 : {}", err);
+                    }
+                }
+                tracing::info!(
+                    "请调用 RequestSms 发送短信验证码后调用 SubmitSmsCode 提交, 或者打开验证地址完成验证后调用 ConfirmDeviceLock"
+                );
+                resp = loop {
+                    match bot.login.wait_device_lock().await? {
+                        DeviceLockAction::RequestSms => match rq_client
+                            .request_sms()
+                            .await
+                            .with_context(|| "请求短信验证码失败")?
+                        {
+                            // 短信已发送, 继续等待验证码
+                            LoginResponse::DeviceLocked(_) => {
+                                tracing::info!("短信验证码已发送");
+                            }
+                            other => break other,
+                        },
+                        DeviceLockAction::SubmitSmsCode(code) => {
+                            break rq_client
+                                .submit_sms_code(code.as_str())
+                                .await
+                                .with_context(|| "提交短信验证码失败")?;
+                        }
+                        DeviceLockAction::Confirm => return Ok(LoginFlow::Retry),
+                    }
+                };
             }
             LoginResponse::NeedCaptcha(LoginNeedCaptcha {
                 ref verify_url,
@@ -246,27 +469,15 @@ async fn loop_login(rq_client: Arc<ricq::Client>, first: RQResult<LoginResponse>
                 ..
             }) => {
                 tracing::info!("滑动条 (原URL) : {:?}", verify_url);
-                let helper_url = verify_url
+                let verify_url = verify_url
                     .clone()
-                    .unwrap()
-                    .replace("ssl.captcha.qq.com", "txhelper.glitch.me");
-                tracing::info!("滑动条 (改URL) : {:?}", helper_url);
-                let mut txt = http_get(&helper_url)
+                    .with_context(|| "未能取得滑动条验证地址")?;
+                let ticket = captcha.solve(bot, verify_url.as_str()).await?;
+                tracing::info!("获取到ticket : {}", ticket);
+                resp = rq_client
+                    .submit_ticket(&ticket)
                     .await
-                    .with_context(|| "http请求失败")?;
-                tracing::info!("您需要使用该仓库 提供的APP进行滑动 , 滑动后请等待, https://github.com/mzdluo123/TxCaptchaHelper : {}", txt);
-                loop {
-                    sleep(Duration::from_secs(5)).await;
-                    let rsp = http_get(&helper_url)
-                        .await
-                        .with_context(|| "http请求失败")?;
-                    if !rsp.eq(&txt) {
-                        txt = rsp;
-                        break;
-                    }
-                }
-                tracing::info!("获取到ticket : {}", txt);
-                resp = rq_client.submit_ticket(&txt).await.expect("发送ticket失败");
+                    .with_context(|| "发送ticket失败")?;
             }
             LoginResponse::DeviceLockLogin { .. } => {
                 resp = rq_client
@@ -295,65 +506,35 @@ async fn loop_login(rq_client: Arc<ricq::Client>, first: RQResult<LoginResponse>
     }
 }
 
-async fn http_get(url: &str) -> Result<String> {
-    Ok(reqwest::ClientBuilder::new().build().unwrap().get(url).header(
-        "user-agent", "Mozilla/5.0 (Linux; Android 6.0; Nexus 5 Build/MRA58N) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/98.0.4758.80 Mobile Safari/537.36",
-    ).send().await?
-        .text()
-        .await?)
-}
-
-fn print_qr_to_console(buff: &Bytes) -> Result<()> {
+fn decode_qr(buff: &Bytes) -> Result<String> {
     let img = image::load_from_memory(buff)?.into_luma8();
     let mut img = rqrr::PreparedImage::prepare(img);
     let grids = img.detect_grids();
     let (_, content) = grids.get(0).with_context(|| "未能识别出二维码")?.decode()?;
-    qr2term::print_qr(content.as_str())?;
-    Ok(())
-}
-
-pub fn token_to_bytes(t: &Token) -> Bytes {
-    let mut token = BytesMut::with_capacity(1024);
-    token.put_i64(t.uin);
-    token.write_bytes_short(&t.d2);
-    token.write_bytes_short(&t.d2key);
-    token.write_bytes_short(&t.tgt);
-    token.write_bytes_short(&t.srm_token);
-    token.write_bytes_short(&t.t133);
-    token.write_bytes_short(&t.encrypted_a1);
-    token.write_bytes_short(&t.wt_session_ticket_key);
-    token.write_bytes_short(&t.out_packet_session_id);
-    token.write_bytes_short(&t.tgtgt_key);
-    token.freeze()
-}
-
-pub fn bytes_to_token(token: Vec<u8>) -> Token {
-    let mut t = Bytes::from(token);
-    Token {
-        uin: t.get_i64(),
-        d2: t.read_bytes_short().to_vec(),
-        d2key: t.read_bytes_short().to_vec(),
-        tgt: t.read_bytes_short().to_vec(),
-        srm_token: t.read_bytes_short().to_vec(),
-        t133: t.read_bytes_short().to_vec(),
-        encrypted_a1: t.read_bytes_short().to_vec(),
-        wt_session_ticket_key: t.read_bytes_short().to_vec(),
-        out_packet_session_id: t.read_bytes_short().to_vec(),
-        tgtgt_key: t.read_bytes_short().to_vec(),
-    }
+    Ok(content)
 }
 
-async fn re_connection(client: Arc<ricq::Client>) -> Result<JoinHandle<()>> {
-    let mut times = 0;
+/// 按backoff的间隔重连, 返回新的连接, 次数用尽时返回错误
+async fn re_connection(
+    bot: &Bot,
+    addresses: &mut AddressPool,
+    backoff: &mut Backoff<'_>,
+) -> Result<JoinHandle<()>> {
     loop {
-        times += 1;
-        let d = Duration::from_secs(1) + Duration::from_secs(min(5, times - 1));
-        tracing::info!("{}秒后进行{}次重连", d.as_secs(), times);
-        sleep(d).await;
-        let res = connection(client.clone()).await;
-        match res {
-            Ok(jh) => return Ok(jh),
-            Err(_) => (),
+        let delay = backoff
+            .next_delay()
+            .with_context(|| format!("重连{}次均失败", backoff.attempt()))?;
+        let attempt = backoff.attempt();
+        tracing::info!("{}毫秒后进行第{}次重连", delay.as_millis(), attempt);
+        bot.emit(BotEvent::Reconnecting(obj::ReconnectingEvent {
+            attempt,
+            delay: delay.as_millis() as i64,
+        }))
+        .await;
+        sleep(delay).await;
+        match connection(&bot.client, addresses).await {
+            Ok(handle) => return Ok(handle),
+            Err(err) => tracing::warn!("第{}次重连失败 : {:?}", attempt, err),
         }
     }
 }
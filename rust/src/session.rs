@@ -0,0 +1,130 @@
+use jni::objects::GlobalRef;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Runtime;
+use tokio::sync::Notify;
+
+use crate::error::NativeError;
+use crate::event::BotEvent;
+use crate::lifecycle::Lifecycle;
+use crate::obj::enums::ErrorType;
+use crate::queue::EventQueue;
+use crate::run::LoginInteraction;
+use crate::status::Status;
+use crate::store::SessionStore;
+
+/// 一个daemon持有的运行时和机器人, Java侧只持有它的句柄
+pub(crate) struct Session {
+    pub runtime: Runtime,
+    pub bot: Arc<Bot>,
+    pub calls: Arc<PendingCalls>,
+}
+
+/// nativeCallAsync传入的还没有complete的CompletableFuture, 运行时关闭前需要全部complete
+#[derive(Default)]
+pub(crate) struct PendingCalls {
+    state: Mutex<PendingState>,
+    idle: Notify,
+}
+
+#[derive(Default)]
+struct PendingState {
+    futures: HashMap<u64, GlobalRef>,
+    next: u64,
+    closed: bool,
+}
+
+impl PendingCalls {
+    /// daemon已经结束时返回Err, 调用方需要立即complete
+    pub(crate) fn insert(&self, future: GlobalRef) -> Result<u64, GlobalRef> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err(future);
+        }
+        state.next += 1;
+        let id = state.next;
+        state.futures.insert(id, future);
+        Ok(id)
+    }
+
+    /// 取出后由调用方complete, 已经被close取走时返回None
+    pub(crate) fn take(&self, id: u64) -> Option<GlobalRef> {
+        let mut state = self.state.lock().unwrap();
+        let future = state.futures.remove(&id);
+        if state.futures.is_empty() {
+            self.idle.notify_waiters();
+        }
+        future
+    }
+
+    /// 等待所有调用完成
+    pub(crate) async fn wait_idle(&self) {
+        loop {
+            let idle = self.idle.notified();
+            if self.state.lock().unwrap().futures.is_empty() {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    /// 不再接收新的调用, 返回还没有complete的future
+    pub(crate) fn close(&self) -> Vec<GlobalRef> {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.futures.drain().map(|(_, future)| future).collect()
+    }
+}
+
+/// 在异步任务之间共享的机器人状态
+pub(crate) struct Bot {
+    pub client: Arc<ricq::Client>,
+    pub login: LoginInteraction,
+    pub events: Arc<EventQueue>,
+    pub session_store: Box<dyn SessionStore + Send + Sync>,
+    pub lifecycle: Lifecycle,
+    pub status: Arc<Status>,
+}
+
+impl Bot {
+    /// 发送ricq之外产生的事件, 例如登录流程中的二维码和验证码
+    pub async fn emit(&self, event: BotEvent) {
+        self.events.push(event, self.status.uin()).await;
+    }
+}
+
+lazy_static! {
+    static ref SESSIONS: Mutex<HashMap<i64, Arc<Session>>> = Mutex::new(HashMap::new());
+}
+
+// 句柄只增不减, 关闭的句柄不会被复用
+static NEXT_HANDLE: AtomicI64 = AtomicI64::new(1);
+
+pub(crate) fn register(session: Arc<Session>) -> i64 {
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::SeqCst);
+    SESSIONS.lock().unwrap().insert(handle, session);
+    handle
+}
+
+pub(crate) fn get(handle: i64) -> Result<Arc<Session>, NativeError> {
+    if let Some(session) = SESSIONS.lock().unwrap().get(&handle) {
+        return Ok(session.clone());
+    }
+    if handle > 0 && handle < NEXT_HANDLE.load(Ordering::SeqCst) {
+        Err(NativeError::new(
+            ErrorType::InvalidHandle,
+            format!("session handle {handle} is closed"),
+        ))
+    } else {
+        Err(NativeError::new(
+            ErrorType::InvalidHandle,
+            format!("unknown session handle {handle}"),
+        ))
+    }
+}
+
+pub(crate) fn close(handle: i64) -> Option<Arc<Session>> {
+    SESSIONS.lock().unwrap().remove(&handle)
+}
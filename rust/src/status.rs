@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicI32, AtomicI64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::obj;
+use crate::obj::enums::ConnectionState;
+use crate::queue::EventQueue;
+
+/// run_ricq和JHandler记录的机器人状态, 供GetStatus查询
+pub(crate) struct Status {
+    state: AtomicI32,
+    uin: AtomicI64,
+    online_time: AtomicI64,
+    last_activity: AtomicI64,
+    reconnect_count: AtomicI32,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self {
+            state: AtomicI32::new(ConnectionState::Connecting as i32),
+            uin: AtomicI64::new(0),
+            online_time: AtomicI64::new(0),
+            last_activity: AtomicI64::new(0),
+            reconnect_count: AtomicI32::new(0),
+        }
+    }
+}
+
+impl Status {
+    pub(crate) fn state(&self) -> ConnectionState {
+        ConnectionState::from_i32(self.state.load(Ordering::SeqCst))
+            .unwrap_or(ConnectionState::Connecting)
+    }
+
+    pub(crate) fn set_state(&self, state: ConnectionState) {
+        let previous = self.state.swap(state as i32, Ordering::SeqCst);
+        if state == ConnectionState::Online && previous != state as i32 {
+            self.online_time
+                .store(now_millis() / 1000, Ordering::SeqCst);
+            self.record_activity();
+        } else if state != ConnectionState::Online {
+            self.online_time.store(0, Ordering::SeqCst);
+        }
+    }
+
+    /// 登录成功的机器人, 分发事件时传递给Java, 登录前为0
+    pub(crate) fn uin(&self) -> i64 {
+        self.uin.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_uin(&self, uin: i64) {
+        self.uin.store(uin, Ordering::SeqCst);
+    }
+
+    /// 收到服务器推送的事件或主动心跳成功
+    pub(crate) fn record_activity(&self) {
+        self.last_activity.store(now_millis(), Ordering::SeqCst);
+    }
+
+    /// 距离上次记录活动的毫秒数
+    pub(crate) fn idle_millis(&self) -> i64 {
+        now_millis() - self.last_activity.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn reconnected(&self) {
+        self.reconnect_count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn to_proto(
+        &self,
+        uin: i64,
+        nickname: String,
+        events: &EventQueue,
+    ) -> obj::BotStatus {
+        obj::BotStatus {
+            state: self.state() as i32,
+            uin,
+            nickname,
+            online_time: self.online_time.load(Ordering::SeqCst),
+            last_activity: self.last_activity.load(Ordering::SeqCst),
+            reconnect_count: self.reconnect_count.load(Ordering::SeqCst),
+            event_queue_length: events.len() as i64,
+            dropped_events: events.dropped(),
+        }
+    }
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,182 @@
+use anyhow::{anyhow, Result};
+use jni::objects::{GlobalRef, JByteArray, JObject};
+use jni::{JNIEnv, JavaVM};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::crypto::{self, Opened, StoreCipher};
+use crate::obj;
+use crate::obj::enums::SessionStoreType;
+
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn save_session(&self, data: Vec<u8>) -> Result<()>;
+    async fn load_session(&self) -> Result<Option<Vec<u8>>>;
+    async fn remove_session(&self) -> Result<()>;
+}
+
+/// 根据配置创建会话存储, 选择Java时java_store为Java侧的rijq.framework.SessionStore
+pub(crate) fn session_store(
+    env: &JNIEnv,
+    config: &obj::StoreConfig,
+    java_store: &JObject,
+    cipher: Option<Arc<StoreCipher>>,
+) -> Result<Box<dyn SessionStore + Send + Sync>> {
+    let path = if config.session_path.is_empty() {
+        "rijq.session"
+    } else {
+        config.session_path.as_str()
+    };
+    let inner = match SessionStoreType::from_i32(config.session_store) {
+        Some(SessionStoreType::File) => FileSessionStore::boxed(path),
+        Some(SessionStoreType::Bean) => {
+            if java_store.is_null() {
+                return Err(anyhow!("使用Java会话存储需要提供SessionStore"));
+            }
+            Box::new(JavaSessionStore::new(env, java_store, path)?)
+        }
+        None => return Err(anyhow!("未知的会话存储方式 : {}", config.session_store)),
+    };
+    Ok(Box::new(SealedSessionStore { inner, cipher }))
+}
+
+/// 配置了密钥时加密保存的会话, 读取到明文的旧会话时重新加密保存
+struct SealedSessionStore {
+    inner: Box<dyn SessionStore + Send + Sync>,
+    cipher: Option<Arc<StoreCipher>>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for SealedSessionStore {
+    async fn save_session(&self, data: Vec<u8>) -> Result<()> {
+        let data = crypto::seal(self.cipher.as_deref(), data)?;
+        self.inner.save_session(data).await
+    }
+    async fn load_session(&self) -> Result<Option<Vec<u8>>> {
+        let data = match self.inner.load_session().await? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        match crypto::open(self.cipher.as_deref(), data)? {
+            Opened::Current(data) => Ok(Some(data)),
+            Opened::Legacy(data) => {
+                tracing::info!("会话为明文, 重新加密保存");
+                self.save_session(data.clone()).await?;
+                Ok(Some(data))
+            }
+        }
+    }
+    async fn remove_session(&self) -> Result<()> {
+        self.inner.remove_session().await
+    }
+}
+
+pub struct FileSessionStore {
+    pub path: String,
+}
+
+impl FileSessionStore {
+    pub fn boxed(path: impl Into<String>) -> Box<dyn SessionStore + Send + Sync> {
+        return Box::new(Self { path: path.into() });
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for FileSessionStore {
+    async fn save_session(&self, data: Vec<u8>) -> Result<()> {
+        tokio::fs::write(self.path.as_str(), data).await?;
+        Ok(())
+    }
+    async fn load_session(&self) -> Result<Option<Vec<u8>>> {
+        if Path::new(self.path.as_str()).exists() {
+            Ok(Some(tokio::fs::read(self.path.as_str()).await?))
+        } else {
+            Ok(None)
+        }
+    }
+    async fn remove_session(&self) -> Result<()> {
+        let _ = tokio::fs::remove_file(self.path.as_str()).await;
+        Ok(())
+    }
+}
+
+/// 调用Java侧的SessionStore (例如Spring Bean) 保存会话, 在blocking线程上附加到JVM后调用,
+/// 多个机器人共用一个SessionStore时以会话路径作为key区分
+pub(crate) struct JavaSessionStore {
+    vm: Arc<JavaVM>,
+    store: GlobalRef,
+    key: String,
+}
+
+impl JavaSessionStore {
+    fn new(env: &JNIEnv, store: &JObject, key: &str) -> Result<Self> {
+        Ok(Self {
+            vm: Arc::new(env.get_java_vm()?),
+            store: env.new_global_ref(store)?,
+            key: key.to_string(),
+        })
+    }
+
+    async fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut JNIEnv, &JObject, &JObject) -> jni::errors::Result<T> + Send + 'static,
+    {
+        let vm = self.vm.clone();
+        let store = self.store.clone();
+        let key = self.key.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut env = vm.attach_current_thread_as_daemon()?;
+            let result = env.with_local_frame(8, |env| {
+                let key = env.new_string(key)?;
+                f(env, store.as_obj(), &key)
+            });
+            match result {
+                Ok(value) => Ok(value),
+                Err(jni::errors::Error::JavaException) => {
+                    let _ = env.exception_describe();
+                    let _ = env.exception_clear();
+                    Err(anyhow!("Java SessionStore 抛出了异常"))
+                }
+                Err(err) => Err(err.into()),
+            }
+        })
+        .await?
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for JavaSessionStore {
+    async fn save_session(&self, data: Vec<u8>) -> Result<()> {
+        self.call(move |env, store, key| {
+            let data = env.byte_array_from_slice(data.as_slice())?;
+            env.call_method(
+                store,
+                "save",
+                "(Ljava/lang/String;[B)V",
+                &[key.into(), (&data).into()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+    async fn load_session(&self) -> Result<Option<Vec<u8>>> {
+        self.call(|env, store, key| {
+            let data = env
+                .call_method(store, "load", "(Ljava/lang/String;)[B", &[key.into()])?
+                .l()?;
+            if data.is_null() {
+                return Ok(None);
+            }
+            Ok(Some(env.convert_byte_array(JByteArray::from(data))?))
+        })
+        .await
+    }
+    async fn remove_session(&self) -> Result<()> {
+        self.call(|env, store, key| {
+            env.call_method(store, "remove", "(Ljava/lang/String;)V", &[key.into()])?;
+            Ok(())
+        })
+        .await
+    }
+}
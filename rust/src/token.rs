@@ -0,0 +1,194 @@
+use anyhow::{anyhow, Context, Result};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use ricq::client::Token;
+use ricq_core::binary::BinaryWriter;
+
+/// 会话格式: MAGIC(4) + VERSION(1) + uin(8) + 9个u16长度前缀的字段 + crc32(4)
+const MAGIC: &[u8; 4] = b"RJTK";
+const VERSION: u8 = 1;
+const CHECKSUM_LEN: usize = 4;
+
+pub fn token_to_bytes(t: &Token) -> Bytes {
+    let mut token = BytesMut::with_capacity(1024);
+    token.put_slice(MAGIC);
+    token.put_u8(VERSION);
+    token.put_i64(t.uin);
+    token.write_bytes_short(&t.d2);
+    token.write_bytes_short(&t.d2key);
+    token.write_bytes_short(&t.tgt);
+    token.write_bytes_short(&t.srm_token);
+    token.write_bytes_short(&t.t133);
+    token.write_bytes_short(&t.encrypted_a1);
+    token.write_bytes_short(&t.wt_session_ticket_key);
+    token.write_bytes_short(&t.out_packet_session_id);
+    token.write_bytes_short(&t.tgtgt_key);
+    let checksum = crc32fast::hash(&token);
+    token.put_u32(checksum);
+    token.freeze()
+}
+
+/// 解析会话, 数据被截断或损坏时返回错误, 没有MAGIC的数据按旧格式解析
+pub fn bytes_to_token(data: &[u8]) -> Result<Token> {
+    if !data.starts_with(MAGIC) {
+        return decode_fields(data).with_context(|| "旧格式会话解析失败");
+    }
+    if data.len() < MAGIC.len() + 1 + CHECKSUM_LEN {
+        return Err(anyhow!("会话数据过短 : {}", data.len()));
+    }
+    let (body, mut checksum) = data.split_at(data.len() - CHECKSUM_LEN);
+    if crc32fast::hash(body) != checksum.get_u32() {
+        return Err(anyhow!("会话校验失败"));
+    }
+    let version = body[MAGIC.len()];
+    if version != VERSION {
+        return Err(anyhow!("不支持的会话版本 : {}", version));
+    }
+    decode_fields(&body[MAGIC.len() + 1..])
+}
+
+fn decode_fields(mut buf: &[u8]) -> Result<Token> {
+    if buf.len() < 8 {
+        return Err(anyhow!("会话数据过短 : {}", buf.len()));
+    }
+    let token = Token {
+        uin: buf.get_i64(),
+        d2: read_field(&mut buf, "d2")?,
+        d2key: read_field(&mut buf, "d2key")?,
+        tgt: read_field(&mut buf, "tgt")?,
+        srm_token: read_field(&mut buf, "srm_token")?,
+        t133: read_field(&mut buf, "t133")?,
+        encrypted_a1: read_field(&mut buf, "encrypted_a1")?,
+        wt_session_ticket_key: read_field(&mut buf, "wt_session_ticket_key")?,
+        out_packet_session_id: read_field(&mut buf, "out_packet_session_id")?,
+        tgtgt_key: read_field(&mut buf, "tgtgt_key")?,
+    };
+    if !buf.is_empty() {
+        return Err(anyhow!("会话数据末尾有多余的 {} 字节", buf.len()));
+    }
+    Ok(token)
+}
+
+fn read_field(buf: &mut &[u8], name: &str) -> Result<Vec<u8>> {
+    if buf.len() < 2 {
+        return Err(anyhow!("会话字段 {} 缺少长度", name));
+    }
+    let len = buf.get_u16() as usize;
+    if buf.len() < len {
+        return Err(anyhow!(
+            "会话字段 {} 长度为 {}, 剩余 {} 字节",
+            name,
+            len,
+            buf.len()
+        ));
+    }
+    let (field, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(field.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_token(rng: &mut StdRng) -> Token {
+        let mut field = |max: usize| {
+            let len = rng.gen_range(0..=max);
+            (0..len).map(|_| rng.gen()).collect::<Vec<u8>>()
+        };
+        Token {
+            uin: 0,
+            d2: field(256),
+            d2key: field(16),
+            tgt: field(128),
+            srm_token: field(64),
+            t133: field(64),
+            encrypted_a1: field(512),
+            wt_session_ticket_key: field(16),
+            out_packet_session_id: field(8),
+            tgtgt_key: field(16),
+        }
+    }
+
+    fn legacy_bytes(t: &Token) -> Vec<u8> {
+        let mut token = BytesMut::new();
+        token.put_i64(t.uin);
+        for field in [
+            &t.d2,
+            &t.d2key,
+            &t.tgt,
+            &t.srm_token,
+            &t.t133,
+            &t.encrypted_a1,
+            &t.wt_session_ticket_key,
+            &t.out_packet_session_id,
+            &t.tgtgt_key,
+        ] {
+            token.write_bytes_short(field);
+        }
+        token.to_vec()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..200 {
+            let mut token = random_token(&mut rng);
+            token.uin = rng.gen_range(10000..4_000_000_000);
+            let data = token_to_bytes(&token);
+            let decoded = bytes_to_token(&data).unwrap();
+            assert_eq!(decoded.uin, token.uin);
+            assert_eq!(token_to_bytes(&decoded), data);
+        }
+    }
+
+    #[test]
+    fn legacy_format() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut token = random_token(&mut rng);
+        token.uin = 123456789;
+        let decoded = bytes_to_token(&legacy_bytes(&token)).unwrap();
+        assert_eq!(token_to_bytes(&decoded), token_to_bytes(&token));
+    }
+
+    #[test]
+    fn truncated() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let token = random_token(&mut rng);
+        let data = token_to_bytes(&token);
+        for len in 0..data.len() {
+            assert!(bytes_to_token(&data[..len]).is_err(), "len {len}");
+        }
+        let legacy = legacy_bytes(&token);
+        for len in 0..legacy.len() {
+            assert!(bytes_to_token(&legacy[..len]).is_err(), "legacy len {len}");
+        }
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..200 {
+            let token = random_token(&mut rng);
+            let mut data = token_to_bytes(&token).to_vec();
+            // MAGIC被破坏时会按旧格式解析, 这里只破坏MAGIC之后的数据
+            let index = rng.gen_range(MAGIC.len()..data.len());
+            data[index] ^= rng.gen_range(1..=255u8);
+            assert!(bytes_to_token(&data).is_err());
+        }
+    }
+
+    #[test]
+    fn random_input_never_panics() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..10000 {
+            let len = rng.gen_range(0..512);
+            let mut data = (0..len).map(|_| rng.gen()).collect::<Vec<u8>>();
+            if rng.gen_bool(0.5) && data.len() >= MAGIC.len() {
+                data[..MAGIC.len()].copy_from_slice(MAGIC);
+            }
+            let _ = bytes_to_token(&data);
+        }
+    }
+}